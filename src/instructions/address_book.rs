@@ -0,0 +1,159 @@
+use pinocchio::sysvars::rent::Rent;
+use pinocchio::sysvars::Sysvar;
+use pinocchio::{
+    account_info::AccountInfo,
+    instruction::{Seed, Signer},
+    program_error::ProgramError,
+    pubkey::{self, Pubkey},
+    ProgramResult,
+};
+use pinocchio_log::log;
+
+use crate::state::{AddressBook, AddressBookEntry, FixedPayload, Multisig, UpdateAddressBook};
+
+/// Checks that `book_account` is the multisig's address book PDA and loads it
+/// Returns `None` if the book has not been created yet
+pub fn load_address_book<'a>(
+    multisig_account: &AccountInfo,
+    book_account: &'a AccountInfo,
+) -> Result<Option<&'a mut AddressBook>, ProgramError> {
+    let (pda_book, _) = pubkey::find_program_address(
+        &[b"address_book", multisig_account.key().as_ref()],
+        &crate::ID,
+    );
+    if &pda_book != book_account.key() {
+        log!("Error: Invalid address book PDA.");
+        return Err(ProgramError::InvalidArgument);
+    }
+
+    if book_account.owner() != &crate::ID {
+        return Ok(None);
+    }
+    Ok(Some(AddressBook::from_account_info(book_account)?))
+}
+
+/// Returns the approvals a proposal paying `amount` to `recipient` needs
+/// Payments to recipients outside the book, or above their cap, need the
+/// book's unlisted threshold when it is higher than the vault threshold
+pub fn transfer_threshold(
+    multisig_account: &AccountInfo,
+    book_account: &AccountInfo,
+    recipient: &Pubkey,
+    amount: u64,
+    vault_threshold: u64,
+) -> Result<u64, ProgramError> {
+    match load_address_book(multisig_account, book_account)? {
+        Some(book) if !book.allows(recipient, amount) => {
+            log!("Recipient is outside the address book. Threshold: {}", book.unlisted_threshold);
+            Ok(vault_threshold.max(book.unlisted_threshold))
+        }
+        _ => Ok(vault_threshold),
+    }
+}
+
+/// Returns the approvals a proposal whose recipients the book cannot check needs
+/// (batches): the book's unlisted threshold when it is higher than the vault threshold
+pub fn unlisted_threshold(
+    multisig_account: &AccountInfo,
+    book_account: &AccountInfo,
+    vault_threshold: u64,
+) -> Result<u64, ProgramError> {
+    match load_address_book(multisig_account, book_account)? {
+        Some(book) => Ok(vault_threshold.max(book.unlisted_threshold)),
+        None => Ok(vault_threshold),
+    }
+}
+
+/// Executes an `UpdateAddressBook` proposal, creating the book on first use
+/// Accounts expected (after the common execution accounts)
+/// - address book (`[b"address_book", multisig]`), system program
+pub fn execute_update_address_book(
+    payload: &[u8],
+    payer: &AccountInfo,
+    multisig_account: &AccountInfo,
+    multisig: &Multisig,
+    remaining: &[AccountInfo],
+) -> ProgramResult {
+    let update = UpdateAddressBook::from_bytes(payload)?;
+    let recipient = update.recipient;
+    let value = update.value;
+
+    let [book_account, _system_program, ..] = remaining else {
+        log!("Error: Not enough accounts provided to update the address book.");
+        return Err(ProgramError::NotEnoughAccountKeys);
+    };
+
+    let book = match load_address_book(multisig_account, book_account)? {
+        Some(book) => book,
+        None => {
+            log!("Creating address book");
+            let (_, bump) = pubkey::find_program_address(
+                &[b"address_book", multisig_account.key().as_ref()],
+                &crate::ID,
+            );
+            let bump_seed = [bump];
+            let seeds = [
+                Seed::from(b"address_book"),
+                Seed::from(multisig_account.key().as_ref()),
+                Seed::from(&bump_seed),
+            ];
+
+            pinocchio_system::instructions::CreateAccount {
+                from: payer,
+                to: book_account,
+                lamports: Rent::get()?.minimum_balance(AddressBook::LEN),
+                space: AddressBook::LEN as u64,
+                owner: &crate::ID,
+            }
+            .invoke_signed(&[Signer::from(&seeds)])?;
+
+            let book = AddressBook::from_account_info(book_account)?;
+            book.multisig = *multisig_account.key();
+            book.bump = bump;
+            book
+        }
+    };
+
+    match update.action {
+        0 => match book.position(&recipient) {
+            Some(index) => {
+                book.entries[index].cap = value;
+                log!("Updated recipient cap to {}", value);
+            }
+            None => {
+                if book.entry_count as usize == AddressBook::MAX_ENTRIES {
+                    log!("Error: The address book is full.");
+                    return Err(ProgramError::AccountDataTooSmall);
+                }
+                book.entries[book.entry_count as usize] = AddressBookEntry { recipient, cap: value };
+                book.entry_count += 1;
+                log!("Added recipient with cap {}", value);
+            }
+        },
+        1 => {
+            let Some(index) = book.position(&recipient) else {
+                log!("Error: Recipient is not in the address book.");
+                return Err(ProgramError::InvalidArgument);
+            };
+            // Swap in the last entry to keep the list contiguous
+            let last = book.entry_count as usize - 1;
+            book.entries[index] = book.entries[last];
+            book.entry_count -= 1;
+            log!("Removed recipient");
+        }
+        2 => {
+            if value > multisig.member_count as u64 {
+                log!("Error: Threshold cannot exceed the member count.");
+                return Err(ProgramError::InvalidInstructionData);
+            }
+            book.unlisted_threshold = value;
+            log!("Unlisted recipient threshold set to {}", value);
+        }
+        _ => {
+            log!("Error: Invalid address book action.");
+            return Err(ProgramError::InvalidInstructionData);
+        }
+    }
+
+    Ok(())
+}
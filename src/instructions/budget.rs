@@ -0,0 +1,505 @@
+use pinocchio::sysvars::clock::Clock;
+use pinocchio::sysvars::rent::Rent;
+use pinocchio::sysvars::Sysvar;
+use pinocchio::{
+    account_info::AccountInfo,
+    instruction::{Seed, Signer},
+    program_error::ProgramError,
+    pubkey::{self, Pubkey},
+    ProgramResult,
+};
+use pinocchio_log::log;
+
+use crate::instructions::{
+    apply_outflow, load_guard, record_flow, verify_destination, verify_vault, with_guard, with_vault_signer,
+    GuardCall,
+};
+use crate::state::{
+    Budget, BudgetSpend, CreateBudget, FixedPayload, LedgerDirection, LedgerSource, Multisig, Proposal,
+};
+use crate::utils::{create_program_address, transfer_sol, transfer_token};
+
+/// Executes a `CreateBudget` proposal on the proposal's vault
+/// Accounts expected (after the common execution accounts)
+/// - budget (`[b"budget", proposal]`), system program
+pub fn execute_create_budget(
+    payload: &[u8],
+    payer: &AccountInfo,
+    multisig: &Multisig,
+    proposal_account: &AccountInfo,
+    proposal: &Proposal,
+    remaining: &[AccountInfo],
+    clock: &Clock,
+) -> ProgramResult {
+    let create_budget = CreateBudget::from_bytes(payload)?;
+    let owner_count = create_budget.owner_count as usize;
+    let owners = create_budget.owners;
+    let threshold = create_budget.threshold;
+    let expires_at = create_budget.expires_at;
+    let now = clock.unix_timestamp as u64;
+
+    let [budget_account, _system_program, ..] = remaining else {
+        log!("Error: Not enough accounts provided to create the budget.");
+        return Err(ProgramError::NotEnoughAccountKeys);
+    };
+
+    if create_budget.amount == 0 || owner_count == 0 || owner_count > 10 {
+        log!("Error: A budget needs an amount and between 1 and 10 owners.");
+        return Err(ProgramError::InvalidInstructionData);
+    }
+    if threshold == 0 || threshold > owner_count as u64 {
+        log!("Error: Invalid threshold. It must be between 1 and the owner count.");
+        return Err(ProgramError::InvalidInstructionData);
+    }
+    if owners[..owner_count]
+        .iter()
+        .any(|owner| !multisig.member_keys[..multisig.member_count as usize].contains(owner))
+    {
+        log!("Error: Budget owners must be members of the multisig.");
+        return Err(ProgramError::InvalidInstructionData);
+    }
+    if expires_at <= now {
+        log!("Error: The budget must expire after {}.", now);
+        return Err(ProgramError::InvalidInstructionData);
+    }
+
+    let (pda_budget, bump) = pubkey::find_program_address(
+        &[b"budget", proposal_account.key().as_ref()],
+        &crate::ID,
+    );
+    if &pda_budget != budget_account.key() {
+        log!("Error: Invalid budget PDA.");
+        return Err(ProgramError::InvalidArgument);
+    }
+
+    let bump_seed = [bump];
+    let seeds = [
+        Seed::from(b"budget"),
+        Seed::from(proposal_account.key().as_ref()),
+        Seed::from(&bump_seed),
+    ];
+
+    pinocchio_system::instructions::CreateAccount {
+        from: payer,
+        to: budget_account,
+        lamports: Rent::get()?.minimum_balance(Budget::LEN),
+        space: Budget::LEN as u64,
+        owner: &crate::ID,
+    }
+    .invoke_signed(&[Signer::from(&seeds)])?;
+
+    let budget = Budget::from_account_info(budget_account)?;
+    budget.proposal = *proposal_account.key();
+    budget.multisig = proposal.multisig;
+    budget.mint = create_budget.mint;
+    budget.name = create_budget.name;
+    budget.owners = [Pubkey::default(); 10];
+    budget.owners[..owner_count].copy_from_slice(&owners[..owner_count]);
+    budget.owner_count = create_budget.owner_count;
+    budget.amount = create_budget.amount;
+    budget.spent = 0;
+    budget.expires_at = expires_at;
+    budget.period = create_budget.period;
+    budget.period_start = now;
+    budget.threshold = threshold;
+    budget.spend_count = 0;
+    budget.proposal_id = proposal.id;
+    budget.vault_index = proposal.vault_index;
+    budget.vault_bump = proposal.vault_bump;
+    budget.bump = bump;
+
+    log!("Created budget of {} with threshold {}", budget.amount, threshold);
+    Ok(())
+}
+
+/// Spends from a budget. The creator of a spend request approves it, and it is paid from
+/// the budget's vault as soon as the budget's threshold of owners have approved it
+/// The amount is checked against what remains of the budget in the period it is paid in
+/// Instruction data (`data`) expected
+/// - 1 byte: Action, 0 (create a spend request) or 1 (approve an existing one)
+/// - For action 0:
+///   - 32 bytes: The recipient (the destination for SOL, owner of the destination token account for tokens)
+///   - 8 bytes: The amount
+///
+/// Accounts expected
+/// - owner (signer, budget owner), budget, spend request (`[b"budget_spend", budget,
+///   index]`, the next index for action 0), system program, vault, destination, system
+///   or token program, the vault's rate limit (`[b"rate_limit", multisig, vault_index]`,
+///   even if none is set), the multisig's ledger (`[b"ledger", multisig]`, even if not created),
+///   the multisig's guard (`[b"guard", multisig]`, even if not set)
+/// - vault token account holding the mint (tokens only)
+/// - the guard program, if one is set
+pub fn process_spend_budget_instruction(accounts: &[AccountInfo], data: &[u8]) -> ProgramResult {
+    spend_budget(accounts, data, &Clock::get()?)
+}
+
+fn spend_budget(accounts: &[AccountInfo], data: &[u8], clock: &Clock) -> ProgramResult {
+    let [owner, budget_account, spend_account, _system_program, vault, destination, program, rate_limit_account, ledger_account, guard_account, remaining @ ..] =
+        accounts
+    else {
+        log!("Error: Not enough accounts provided. Expected at least 10.");
+        return Err(ProgramError::NotEnoughAccountKeys);
+    };
+
+    if !owner.is_signer() {
+        log!("Error: The owner must sign the transaction.");
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+
+    if budget_account.owner() != &crate::ID {
+        log!("Error: Budget must be owned by this program.");
+        return Err(ProgramError::IllegalOwner);
+    }
+    let budget = Budget::from_account_info(budget_account)?;
+
+    // The budget decides which vault signs, so only the real PDA can be trusted
+    let pda_budget = create_program_address(
+        &[b"budget", budget.proposal.as_ref(), &[budget.bump]],
+        &crate::ID,
+    )?;
+    if &pda_budget != budget_account.key() {
+        log!("Error: Invalid budget PDA.");
+        return Err(ProgramError::InvalidArgument);
+    }
+
+    let Some(position) = budget.owners[..budget.owner_count as usize]
+        .iter()
+        .position(|key| key == owner.key())
+    else {
+        log!("Error: Signer is not an owner of this budget.");
+        return Err(ProgramError::IllegalOwner);
+    };
+    let now = clock.unix_timestamp as u64;
+    if now > budget.expires_at {
+        log!("Error: The budget expired at {}.", budget.expires_at);
+        return Err(ProgramError::InvalidArgument);
+    }
+    budget.roll_period(now);
+
+    let spend = match data.first() {
+        Some(0) => create_spend(owner, budget_account, budget, spend_account, &data[1..])?,
+        Some(1) => load_spend(budget_account, spend_account)?,
+        _ => {
+            log!("Error: Invalid action. Expected 0 (create) or 1 (approve).");
+            return Err(ProgramError::InvalidInstructionData);
+        }
+    };
+
+    if spend.executed == 1 {
+        log!("Error: Spend request has already been paid.");
+        return Err(ProgramError::InvalidAccountData);
+    }
+    if spend.approvals & (1 << position) != 0 {
+        log!("Error: Owner has already approved this spend request.");
+        return Err(ProgramError::InvalidArgument);
+    }
+    spend.approvals |= 1 << position;
+
+    let approvals = spend.approvals.count_ones() as u64;
+    if approvals < budget.threshold {
+        log!("Spend request {} approved ({} of {})", spend.index, approvals, budget.threshold);
+        return Ok(());
+    }
+
+    if spend.amount > budget.remaining() {
+        log!("Error: Amount exceeds the remaining budget of {}.", budget.remaining());
+        return Err(ProgramError::InsufficientFunds);
+    }
+    verify_vault(&budget.multisig, budget.vault_index, budget.vault_bump, vault)?;
+    verify_destination(destination, program, &budget.mint, &spend.recipient)?;
+
+    budget.spent += spend.amount;
+    spend.executed = 1;
+
+    apply_outflow(&budget.multisig, budget.vault_index, rate_limit_account, &budget.mint, spend.amount)?;
+    record_flow(
+        &budget.multisig,
+        ledger_account,
+        budget.vault_index,
+        LedgerDirection::Outflow,
+        LedgerSource::Budget,
+        &budget.mint,
+        spend.amount,
+        &spend.recipient,
+        budget.proposal_id,
+    )?;
+
+    // Approvals carry no recipient or amount, so the guard gets them from the spend request
+    let guard = load_guard(&budget.multisig, guard_account)?;
+    let guard_data = [spend.recipient.as_ref(), &spend.amount.to_le_bytes()].concat();
+    let guard_call = GuardCall {
+        source: LedgerSource::Budget,
+        multisig: &budget.multisig,
+        vault,
+        vault_index: budget.vault_index,
+        authority: budget_account,
+        proposal_id: budget.proposal_id,
+        kind: 0,
+        data: &guard_data,
+    };
+
+    with_guard(guard, &guard_call, remaining, || {
+        with_vault_signer(&budget.multisig, budget.vault_index, budget.vault_bump, |signer| {
+            if budget.mint == Pubkey::default() {
+                transfer_sol(vault, destination, spend.amount, signer)
+            } else {
+                let [source, ..] = remaining else {
+                    log!("Error: Vault token account is required for token budgets.");
+                    return Err(ProgramError::NotEnoughAccountKeys);
+                };
+                transfer_token(source, destination, vault, &budget.mint, program, spend.amount, signer)
+            }
+        })
+    })?;
+
+    log!("Spent {} from the budget. Remaining: {}", spend.amount, budget.remaining());
+    Ok(())
+}
+
+/// Creates the next spend request of a budget
+fn create_spend<'a>(
+    owner: &AccountInfo,
+    budget_account: &AccountInfo,
+    budget: &mut Budget,
+    spend_account: &'a AccountInfo,
+    data: &[u8],
+) -> Result<&'a mut BudgetSpend, ProgramError> {
+    if data.len() < 40 {
+        log!("Error: Instruction data is invalid. Expected a recipient and an amount.");
+        return Err(ProgramError::InvalidInstructionData);
+    }
+    let recipient: Pubkey = data[0..32].try_into().unwrap();
+    let amount = u64::from_le_bytes(data[32..40].try_into().unwrap());
+
+    if amount == 0 || amount > budget.remaining() {
+        log!("Error: Amount must be between 1 and the remaining budget of {}.", budget.remaining());
+        return Err(ProgramError::InvalidInstructionData);
+    }
+
+    let index_seed = budget.spend_count.to_le_bytes();
+    let (pda_spend, bump) = pubkey::find_program_address(
+        &[b"budget_spend", budget_account.key().as_ref(), &index_seed],
+        &crate::ID,
+    );
+    if &pda_spend != spend_account.key() {
+        log!("Error: Invalid spend request PDA.");
+        return Err(ProgramError::InvalidArgument);
+    }
+
+    let bump_seed = [bump];
+    let seeds = [
+        Seed::from(b"budget_spend"),
+        Seed::from(budget_account.key().as_ref()),
+        Seed::from(&index_seed),
+        Seed::from(&bump_seed),
+    ];
+
+    pinocchio_system::instructions::CreateAccount {
+        from: owner,
+        to: spend_account,
+        lamports: Rent::get()?.minimum_balance(BudgetSpend::LEN),
+        space: BudgetSpend::LEN as u64,
+        owner: &crate::ID,
+    }
+    .invoke_signed(&[Signer::from(&seeds)])?;
+
+    let spend = BudgetSpend::from_account_info(spend_account)?;
+    spend.budget = *budget_account.key();
+    spend.recipient = recipient;
+    spend.amount = amount;
+    spend.approvals = 0;
+    spend.index = budget.spend_count;
+    spend.executed = 0;
+    spend.bump = bump;
+    budget.spend_count += 1;
+
+    log!("Created spend request {} for {}", spend.index, amount);
+    Ok(spend)
+}
+
+/// Loads an existing spend request of a budget
+fn load_spend<'a>(budget_account: &AccountInfo, spend_account: &'a AccountInfo) -> Result<&'a mut BudgetSpend, ProgramError> {
+    if spend_account.owner() != &crate::ID {
+        log!("Error: Spend request must be owned by this program.");
+        return Err(ProgramError::IllegalOwner);
+    }
+    let spend = BudgetSpend::from_account_info(spend_account)?;
+    let pda_spend = create_program_address(
+        &[b"budget_spend", budget_account.key().as_ref(), &spend.index.to_le_bytes(), &[spend.bump]],
+        &crate::ID,
+    )?;
+    if spend.budget != *budget_account.key() || &pda_spend != spend_account.key() {
+        log!("Error: Spend request does not belong to this budget.");
+        return Err(ProgramError::InvalidArgument);
+    }
+    Ok(spend)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::ProposalStatus;
+    use crate::test_utils::{multisig_account, program_address, proposal_account, TestAccount};
+
+    const MULTISIG: Pubkey = [0x21; 32];
+    const PROPOSAL: Pubkey = [0x22; 32];
+    const OWNERS: [Pubkey; 3] = [[0x31; 32], [0x32; 32], [0x33; 32]];
+    const RECIPIENT: Pubkey = [0x41; 32];
+    const SYSTEM: Pubkey = pinocchio_system::ID;
+
+    /// A SOL budget of 1_000 from the treasury for the three owners, from 1_000 until 10_000
+    fn budget_account(threshold: u64, period: u64) -> TestAccount {
+        let key = program_address(&[b"budget", &PROPOSAL, &[255]]);
+        let mut account = TestAccount::new(key, crate::ID, Budget::LEN);
+        let budget = account.state::<Budget>();
+        budget.proposal = PROPOSAL;
+        budget.multisig = MULTISIG;
+        budget.owners[..3].copy_from_slice(&OWNERS);
+        budget.owner_count = 3;
+        budget.amount = 1_000;
+        budget.expires_at = 10_000;
+        budget.period = period;
+        budget.period_start = 1_000;
+        budget.threshold = threshold;
+        budget.spend_count = 1;
+        budget.vault_bump = 255;
+        budget.bump = 255;
+        account
+    }
+
+    /// Spend request 0 of the budget, for `amount` to the recipient
+    fn spend_account(budget: &mut TestAccount, amount: u64, approvals: u64) -> TestAccount {
+        let budget_key = *budget.info().key();
+        let key = program_address(&[b"budget_spend", &budget_key, &0u64.to_le_bytes(), &[255]]);
+        let mut account = TestAccount::new(key, crate::ID, BudgetSpend::LEN);
+        let spend = account.state::<BudgetSpend>();
+        spend.budget = budget_key;
+        spend.recipient = RECIPIENT;
+        spend.amount = amount;
+        spend.approvals = approvals;
+        spend.bump = 255;
+        account
+    }
+
+    /// `owner` approves the spend request at `now`, with no rate limit, ledger or guard
+    fn approve(owner: Pubkey, budget: &mut TestAccount, spend: &mut TestAccount, now: i64) -> ProgramResult {
+        let mut owner = TestAccount::new(owner, SYSTEM, 0).signer();
+        let mut system_program = TestAccount::new(SYSTEM, [0x25; 32], 0);
+        let vault_key = program_address(&[b"treasury", &MULTISIG, &[255]]);
+        let mut vault = TestAccount::new(vault_key, SYSTEM, 0).with_lamports(1_000_000);
+        let mut destination = TestAccount::new(RECIPIENT, SYSTEM, 0);
+        let rate_limit_key = program_address(&[b"rate_limit", &MULTISIG, &[0], &[255]]);
+        let mut rate_limit = TestAccount::new(rate_limit_key, SYSTEM, 0);
+        let mut ledger = TestAccount::new(program_address(&[b"ledger", &MULTISIG, &[255]]), SYSTEM, 0);
+        let mut guard = TestAccount::new(program_address(&[b"guard", &MULTISIG, &[255]]), SYSTEM, 0);
+        let accounts = [
+            owner.info(),
+            budget.info(),
+            spend.info(),
+            system_program.info(),
+            vault.info(),
+            destination.info(),
+            system_program.info(),
+            rate_limit.info(),
+            ledger.info(),
+            guard.info(),
+        ];
+
+        let clock = Clock { unix_timestamp: now, ..Clock::default() };
+        spend_budget(&accounts, &[1], &clock)
+    }
+
+    #[test]
+    fn spends_are_paid_once_the_threshold_of_owners_approves() {
+        let mut budget = budget_account(3, 0);
+        let mut spend = spend_account(&mut budget, 300, 0b001);
+
+        assert_eq!(approve(OWNERS[1], &mut budget, &mut spend, 2_000), Ok(()));
+        assert_eq!(spend.state::<BudgetSpend>().executed, 0);
+        assert_eq!(budget.state::<Budget>().spent, 0);
+
+        assert_eq!(approve(OWNERS[2], &mut budget, &mut spend, 2_000), Ok(()));
+        assert_eq!(spend.state::<BudgetSpend>().executed, 1);
+        assert_eq!(budget.state::<Budget>().remaining(), 700);
+
+        // A paid request cannot be paid again
+        assert_eq!(approve(OWNERS[0], &mut budget, &mut spend, 2_000), Err(ProgramError::InvalidAccountData));
+    }
+
+    #[test]
+    fn each_owner_approves_once_and_only_owners_approve() {
+        let mut budget = budget_account(2, 0);
+        let mut spend = spend_account(&mut budget, 300, 0b001);
+
+        assert_eq!(approve(OWNERS[0], &mut budget, &mut spend, 2_000), Err(ProgramError::InvalidArgument));
+        assert_eq!(approve([0x34; 32], &mut budget, &mut spend, 2_000), Err(ProgramError::IllegalOwner));
+        assert_eq!(spend.state::<BudgetSpend>().approvals, 0b001);
+    }
+
+    #[test]
+    fn spends_beyond_the_remaining_budget_are_rejected() {
+        let mut budget = budget_account(2, 0);
+        budget.state::<Budget>().spent = 800;
+        let mut spend = spend_account(&mut budget, 300, 0b001);
+
+        assert_eq!(approve(OWNERS[1], &mut budget, &mut spend, 2_000), Err(ProgramError::InsufficientFunds));
+        assert_eq!(budget.state::<Budget>().spent, 800);
+    }
+
+    #[test]
+    fn nothing_can_be_spent_once_the_budget_expires() {
+        let mut budget = budget_account(2, 0);
+        let mut spend = spend_account(&mut budget, 300, 0b001);
+        assert_eq!(approve(OWNERS[1], &mut budget, &mut spend, 10_001), Err(ProgramError::InvalidArgument));
+
+        let mut spend = spend_account(&mut budget, 300, 0b001);
+        assert_eq!(approve(OWNERS[1], &mut budget, &mut spend, 10_000), Ok(()));
+    }
+
+    #[test]
+    fn the_amount_can_be_spent_again_each_period() {
+        let mut budget = budget_account(2, 1_000);
+        budget.state::<Budget>().spent = 800;
+
+        let mut spend = spend_account(&mut budget, 300, 0b001);
+        assert_eq!(approve(OWNERS[1], &mut budget, &mut spend, 1_999), Err(ProgramError::InsufficientFunds));
+
+        // Periods skipped entirely leave the current one starting on a period boundary
+        let mut spend = spend_account(&mut budget, 300, 0b001);
+        assert_eq!(approve(OWNERS[1], &mut budget, &mut spend, 3_500), Ok(()));
+        let state = budget.state::<Budget>();
+        assert_eq!((state.spent, state.period_start), (300, 3_000));
+    }
+
+    #[test]
+    fn budgets_must_expire_in_the_future() {
+        let mut multisig = multisig_account(&[0x11; 32], &OWNERS, 2);
+        let mut proposal = proposal_account(&MULTISIG, 1, ProposalStatus::Succeeded);
+        let mut payer = TestAccount::new([0x43; 32], SYSTEM, 0).signer();
+        let mut budget = TestAccount::new([0x42; 32], SYSTEM, 0);
+        let mut system_program = TestAccount::new(SYSTEM, [0x25; 32], 0);
+        let (payer, proposal_info) = (payer.info(), proposal.info());
+        let remaining = [budget.info(), system_program.info()];
+        let clock = Clock { unix_timestamp: 5_000, ..Clock::default() };
+
+        for expires_at in [0u64, 4_999, 5_000] {
+            let mut payload = [0u8; CreateBudget::LEN];
+            payload[64..72].copy_from_slice(&1_000u64.to_le_bytes());
+            payload[72..80].copy_from_slice(&expires_at.to_le_bytes());
+            payload[88..96].copy_from_slice(&2u64.to_le_bytes());
+            payload[96] = 3;
+            payload[97..97 + 96].copy_from_slice(&OWNERS.concat());
+
+            let result = execute_create_budget(
+                &payload,
+                &payer,
+                multisig.state::<Multisig>(),
+                &proposal_info,
+                proposal.state::<Proposal>(),
+                &remaining,
+                &clock,
+            );
+            assert_eq!(result, Err(ProgramError::InvalidInstructionData));
+        }
+    }
+}
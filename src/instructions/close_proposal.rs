@@ -1,13 +1,20 @@
 use pinocchio::sysvars::clock::Clock;
+use pinocchio::sysvars::rent::Rent;
 use pinocchio::sysvars::Sysvar;
-use pinocchio::{account_info::AccountInfo, program_error::ProgramError, ProgramResult};
+use pinocchio::{account_info::AccountInfo, program_error::ProgramError, pubkey, ProgramResult};
 use pinocchio_log::log;
 
-use crate::state::{Multisig, Proposal, ProposalStatus};
+use crate::instructions::{load_multisig, load_proposal};
+use crate::state::{Proposal, ProposalKind, ProposalStatus, TimeUnit};
+use crate::utils::sha256;
 
 //Processes closing or cancelling a proposal
 //0: Tally votes for a proposal that has expired or has all votes in
 //1: Cancel an active proposal This can only be done by the proposal creator
+//2: Expire a succeeded proposal whose execution window has lapsed (anyone can crank this)
+//3: Migrate a proposal created before the account grew new fields (the signer pays the
+//   extra rent, and the system program is needed as fourth account). Older proposals
+//   cannot be loaded by any other action until they are migrated
 //Accounts expected
 pub fn process_close_proposal_instruction(accounts: &[AccountInfo], data: &[u8]) -> ProgramResult {
     // Get the action code (0 for Tally, 1 for Cancel).
@@ -23,19 +30,29 @@ pub fn process_close_proposal_instruction(accounts: &[AccountInfo], data: &[u8])
         return Err(ProgramError::MissingRequiredSignature);
     }
 
-    let proposal = Proposal::from_account_info(proposal_account)?;
+    if action == 3 {
+        return migrate_proposal(signer_account, proposal_account, multisig_account, accounts);
+    }
+
+    let proposal = load_proposal(proposal_account)?;
 
     match action {
         0 => {
             log!("Action: Tallying proposal ID: {}", proposal.id);
-            let multisig = Multisig::from_account_info(multisig_account)?;
+            // The threshold must come from the multisig the proposal belongs to
+            if proposal.multisig != *multisig_account.key() {
+                log!("Error: Multisig account does not match the proposal.");
+                return Err(ProgramError::InvalidArgument);
+            }
+            let multisig = load_multisig(multisig_account)?;
             let clock = Clock::get()?;
 
             if proposal.status != ProposalStatus::Active {
                 return Err(ProgramError::InvalidAccountData);
             }
 
-            let is_expired = clock.unix_timestamp as u64 > proposal.expiration_time;
+            let now = proposal.time_unit.current(&clock);
+            let is_expired = now > proposal.expiration_time;
             let eligible_voters = multisig.member_count as usize;
             let votes_cast = proposal.votes[..eligible_voters]
                 .iter()
@@ -54,12 +71,18 @@ pub fn process_close_proposal_instruction(accounts: &[AccountInfo], data: &[u8])
             log!(
                 "Yes votes: {} | Required: {}",
                 yes_votes,
-                multisig.threshold
+                proposal.threshold
             );
 
-            if yes_votes >= multisig.threshold {
+            // Threshold of the target vault, fixed when the proposal was created
+            if yes_votes >= proposal.threshold {
                 proposal.status = ProposalStatus::Succeeded;
-                log!("Outcome: Succeeded");
+                // Members get a window to exit or veto before the proposal can be executed
+                proposal.executable_after = now + proposal.time_lock;
+                if proposal.execution_window > 0 {
+                    proposal.execution_deadline = proposal.executable_after + proposal.execution_window;
+                }
+                log!("Outcome: Succeeded. Executable after: {}", proposal.executable_after);
             } else {
                 proposal.status = ProposalStatus::Failed;
                 log!("Outcome: Failed");
@@ -82,6 +105,25 @@ pub fn process_close_proposal_instruction(accounts: &[AccountInfo], data: &[u8])
             proposal.status = ProposalStatus::Cancelled;
             log!("Outcome: Cancelled");
         }
+        // EXPIRE LOGIC
+        2 => {
+            log!("Action: Expiring proposal ID: {}", proposal.id);
+            if proposal.status != ProposalStatus::Succeeded {
+                log!("Error: Only succeeded proposals can expire.");
+                return Err(ProgramError::InvalidAccountData);
+            }
+
+            let clock = Clock::get()?;
+            let now = proposal.time_unit.current(&clock);
+            if proposal.execution_deadline == 0 || now <= proposal.execution_deadline {
+                log!("Error: Proposal is still within its execution window.");
+                return Err(ProgramError::InvalidArgument);
+            }
+
+            // Terminal: a stale approval can never be replayed
+            proposal.status = ProposalStatus::Expired;
+            log!("Outcome: Expired");
+        }
         _ => {
             log!("Error: Invalid action code.");
             return Err(ProgramError::InvalidInstructionData);
@@ -90,3 +132,68 @@ pub fn process_close_proposal_instruction(accounts: &[AccountInfo], data: &[u8])
 
     Ok(())
 }
+
+/// Grows a proposal created with the original layout to `Proposal::LEN`
+/// Fields were only appended, so the existing bytes keep their meaning. The new ones are
+/// set to how such a proposal behaved: a Standard proposal on the treasury, timed in unix
+/// seconds, tallied against the multisig's threshold, with no quiet period or time lock
+fn migrate_proposal(
+    payer: &AccountInfo,
+    proposal_account: &AccountInfo,
+    multisig_account: &AccountInfo,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    if proposal_account.owner() != &crate::ID {
+        log!("Error: Proposal must be owned by this program.");
+        return Err(ProgramError::IllegalOwner);
+    }
+    if proposal_account.data_len() != Proposal::LEGACY_LEN {
+        log!("Error: Only proposals with the original layout can be migrated.");
+        return Err(ProgramError::InvalidAccountData);
+    }
+    if accounts.get(3).map(|account| account.key()) != Some(&pinocchio_system::ID) {
+        log!("Error: The system program is required to migrate this proposal.");
+        return Err(ProgramError::NotEnoughAccountKeys);
+    }
+
+    let multisig = load_multisig(multisig_account)?;
+
+    // Older proposals do not record their multisig, the PDA proves which one they belong to
+    let id: [u8; 8] = proposal_account.try_borrow_data()?[32..40].try_into().unwrap();
+    let (pda_proposal, bump) = pubkey::find_program_address(
+        &[b"proposal", multisig_account.key().as_ref(), &id],
+        &crate::ID,
+    );
+    if &pda_proposal != proposal_account.key() {
+        log!("Error: Proposal does not belong to this multisig.");
+        return Err(ProgramError::InvalidArgument);
+    }
+
+    log!("Migrating proposal from {} to {} bytes", proposal_account.data_len(), Proposal::LEN);
+
+    let rent = Rent::get()?.minimum_balance(Proposal::LEN);
+    let shortfall = rent.saturating_sub(proposal_account.lamports());
+    if shortfall > 0 {
+        pinocchio_system::instructions::Transfer {
+            from: payer,
+            to: proposal_account,
+            lamports: shortfall,
+        }
+        .invoke()?;
+    }
+    proposal_account.resize(Proposal::LEN)?;
+
+    let proposal = Proposal::from_account_info(proposal_account)?;
+    proposal.voting_starts_at = 0;
+    proposal.time_unit = TimeUnit::UnixTimestamp;
+    proposal.multisig = *multisig_account.key();
+    proposal.kind = ProposalKind::Standard;
+    proposal.threshold = multisig.threshold;
+    proposal.vault_index = 0;
+    proposal.vault_bump = multisig.treasury_bump;
+    proposal.payload_hash = sha256(&[]);
+    proposal.bump = bump;
+
+    log!("Proposal {} migrated.", proposal.id);
+    Ok(())
+}
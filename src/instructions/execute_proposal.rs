@@ -0,0 +1,420 @@
+use pinocchio::sysvars::clock::Clock;
+use pinocchio::sysvars::Sysvar;
+use pinocchio::{
+    account_info::AccountInfo,
+    cpi::slice_invoke_signed,
+    instruction::{AccountMeta, Instruction, Signer},
+    program_error::ProgramError,
+    pubkey::Pubkey,
+    ProgramResult,
+};
+use pinocchio_log::log;
+
+use crate::instructions::{
+    apply_outflow, execute_approve_message, execute_cancel_milestone_escrow, execute_cancel_payment_schedule,
+    execute_cancel_stream, execute_create_budget, execute_create_milestone_escrow,
+    execute_create_payment_schedule, execute_create_spending_limit, execute_create_stake,
+    execute_create_stream, execute_create_vault, execute_deactivate_stake, execute_delegate_stake,
+    execute_program_upgrade, execute_set_guard, execute_set_rate_limit, execute_set_upgrade_authority,
+    execute_transfer, execute_update_address_book, execute_update_modules,
+    execute_update_program_allowlist, execute_withdraw_stake, is_program_allowed, load_guard,
+    load_multisig, load_payload, load_program_allowlist, load_proposal, load_rate_limit, record_flow,
+    verify_vault, with_guard, with_vault_signer, GuardCall, VaultBalances,
+};
+use crate::state::{
+    Batch, CancelMilestoneEscrow, CancelStream, FixedPayload, LedgerDirection, LedgerSource,
+    ProgramAllowlist, ProposalKind, ProposalStatus, Transfer, WithdrawStake,
+};
+use crate::utils::{TOKEN_2022_PROGRAM_ID, TOKEN_PROGRAM_ID};
+
+const ADDRESS_LOOKUP_TABLE_PROGRAM_ID: Pubkey =
+    pinocchio_pubkey::pubkey!("AddressLookupTab1e1111111111111111111111111");
+
+/// Size of the lookup table header preceding its addresses
+const LOOKUP_TABLE_META_SIZE: usize = 56;
+
+/// Executes the payload of a succeeded proposal with its target vault PDA as signer
+/// Any multisig member can trigger execution once the time lock has passed and
+/// before the execution window lapses. A failing CPI fails the whole proposal.
+/// Accounts expected
+/// - executor (signer, member), multisig, proposal, vault (the proposal's target, the
+///   treasury for vault 0), payload, the vault's rate limit (`[b"rate_limit", multisig,
+///   vault_index]`, even if none is set), the multisig's ledger (`[b"ledger", multisig]`,
+///   even if not created), the multisig's guard (`[b"guard", multisig]`, even if not set),
+///   the multisig's program allowlist (`[b"program_allowlist", multisig]`, even if not created)
+/// - the accounts required by the proposal kind (see the `execute_*` function for the kind)
+/// - the guard program, if one is set (anywhere after the common accounts)
+///
+/// Whatever leaves the vault during execution counts against its rate limit, and every
+/// flow in or out of the vault is recorded to the ledger. If a guard is set, it is called
+/// before and after the payload runs and can fail the execution (`SetGuard` proposals
+/// skip it, so a faulty guard can always be replaced). Every program the vault signs a
+/// call to must be on the allowlist, including the upgradeable loader and the stake program
+pub fn process_execute_proposal_instruction(accounts: &[AccountInfo], _data: &[u8]) -> ProgramResult {
+    let [executor, multisig_account, proposal_account, vault, payload_account, rate_limit_account, ledger_account, guard_account, allowlist_account, remaining @ ..] =
+        accounts
+    else {
+        log!("Error: Not enough accounts provided. Expected at least 9.");
+        return Err(ProgramError::NotEnoughAccountKeys);
+    };
+
+    if !executor.is_signer() {
+        log!("Error: The executor must sign the transaction.");
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+
+    let multisig = load_multisig(multisig_account)?;
+    let proposal = load_proposal(proposal_account)?;
+
+    if proposal.multisig != *multisig_account.key() {
+        log!("Error: Proposal does not belong to this multisig.");
+        return Err(ProgramError::InvalidArgument);
+    }
+
+    if !multisig.member_keys[..multisig.member_count as usize].contains(executor.key()) {
+        log!("Error: Executor is not a member of the multisig.");
+        return Err(ProgramError::IllegalOwner);
+    }
+
+    if proposal.status != ProposalStatus::Succeeded {
+        log!("Error: Only succeeded proposals can be executed.");
+        return Err(ProgramError::InvalidAccountData);
+    }
+
+    // Proposals created before a threshold was set could succeed without a single vote
+    if proposal.threshold == 0 {
+        log!("Error: Proposal was approved without a threshold.");
+        return Err(ProgramError::InvalidAccountData);
+    }
+
+    let clock = Clock::get()?;
+    let now = proposal.time_unit.current(&clock);
+    if now < proposal.executable_after {
+        log!("Error: Proposal is time locked until {}.", proposal.executable_after);
+        return Err(ProgramError::InvalidArgument);
+    }
+    if proposal.execution_deadline != 0 && now > proposal.execution_deadline {
+        log!("Error: The execution window for this proposal has lapsed.");
+        return Err(ProgramError::InvalidArgument);
+    }
+
+    verify_vault(multisig_account.key(), proposal.vault_index, proposal.vault_bump, vault)?;
+
+    let payload = load_payload(proposal_account, payload_account)?;
+
+    // Marked before any CPI so a re-entrant call cannot execute the proposal twice
+    proposal.status = ProposalStatus::Executed;
+
+    let vault_index = proposal.vault_index;
+    let rate_limited = load_rate_limit(multisig_account.key(), vault_index, rate_limit_account)?
+        .is_some_and(|rate_limit| rate_limit.limit_count > 0);
+    let allowlist = load_program_allowlist(multisig_account.key(), allowlist_account)?
+        .map(|allowlist| &*allowlist);
+    let balances_before = VaultBalances::snapshot(vault, remaining);
+
+    let guard = match proposal.kind {
+        ProposalKind::SetGuard => None,
+        _ => load_guard(multisig_account.key(), guard_account)?,
+    };
+    let guard_call = GuardCall {
+        source: LedgerSource::Proposal,
+        multisig: multisig_account.key(),
+        vault,
+        vault_index,
+        authority: proposal_account,
+        proposal_id: proposal.id,
+        kind: proposal.kind as u8,
+        data: payload,
+    };
+
+    with_guard(guard, &guard_call, remaining, || {
+        with_vault_signer(
+            multisig_account.key(),
+            proposal.vault_index,
+            proposal.vault_bump,
+            |signer| match proposal.kind {
+                ProposalKind::Batch => {
+                    execute_batch(payload, vault, allowlist, rate_limited, remaining, signer)
+                }
+                ProposalKind::ProgramUpgrade => {
+                    execute_program_upgrade(payload, vault, allowlist, remaining, signer)
+                }
+                ProposalKind::SetUpgradeAuthority => {
+                    execute_set_upgrade_authority(payload, vault, allowlist, remaining, signer)
+                }
+                ProposalKind::CreateVault => {
+                    execute_create_vault(payload, executor, multisig_account, multisig, remaining)
+                }
+                ProposalKind::Transfer => execute_transfer(payload, vault, allowlist, remaining, signer),
+                ProposalKind::UpdateAddressBook => {
+                    execute_update_address_book(payload, executor, multisig_account, multisig, remaining)
+                }
+                ProposalKind::SetRateLimit => execute_set_rate_limit(
+                    payload,
+                    executor,
+                    multisig_account.key(),
+                    vault_index,
+                    rate_limit_account,
+                ),
+                ProposalKind::CreatePaymentSchedule => execute_create_payment_schedule(
+                    payload,
+                    executor,
+                    multisig_account,
+                    proposal_account,
+                    proposal,
+                    remaining,
+                ),
+                ProposalKind::CreateStream => execute_create_stream(
+                    payload,
+                    executor,
+                    multisig_account,
+                    vault,
+                    proposal_account,
+                    proposal,
+                    allowlist,
+                    remaining,
+                    signer,
+                ),
+                ProposalKind::CancelPaymentSchedule => {
+                    execute_cancel_payment_schedule(payload, multisig_account, proposal, remaining)
+                }
+                ProposalKind::CancelStream => {
+                    execute_cancel_stream(payload, multisig_account, vault, proposal, remaining)
+                }
+                ProposalKind::CreateMilestoneEscrow => execute_create_milestone_escrow(
+                    payload,
+                    executor,
+                    multisig,
+                    vault,
+                    proposal_account,
+                    proposal,
+                    allowlist,
+                    remaining,
+                    signer,
+                ),
+                ProposalKind::CancelMilestoneEscrow => execute_cancel_milestone_escrow(
+                    payload,
+                    multisig_account,
+                    vault,
+                    proposal,
+                    remaining,
+                    &clock,
+                ),
+                ProposalKind::CreateBudget => execute_create_budget(
+                    payload,
+                    executor,
+                    multisig,
+                    proposal_account,
+                    proposal,
+                    remaining,
+                    &clock,
+                ),
+                ProposalKind::CreateStake => {
+                    execute_create_stake(payload, vault, proposal.id, allowlist, remaining, signer)
+                }
+                ProposalKind::DelegateStake => {
+                    execute_delegate_stake(payload, vault, allowlist, remaining, signer)
+                }
+                ProposalKind::DeactivateStake => {
+                    execute_deactivate_stake(payload, vault, allowlist, remaining, signer)
+                }
+                ProposalKind::WithdrawStake => {
+                    execute_withdraw_stake(payload, vault, allowlist, remaining, signer)
+                }
+                ProposalKind::ApproveMessage => {
+                    execute_approve_message(payload, executor, multisig_account, proposal, remaining)
+                }
+                ProposalKind::UpdateProgramAllowlist => execute_update_program_allowlist(
+                    payload,
+                    executor,
+                    multisig_account,
+                    allowlist_account,
+                    remaining,
+                ),
+                ProposalKind::UpdateModules => execute_update_modules(payload, multisig),
+                ProposalKind::SetGuard => execute_set_guard(payload, executor, multisig_account, guard_account),
+                ProposalKind::CreateSpendingLimit => execute_create_spending_limit(
+                    payload,
+                    executor,
+                    multisig_account,
+                    multisig,
+                    proposal_account,
+                    proposal,
+                    remaining,
+                ),
+                ProposalKind::ParentVote => {
+                    log!("Error: Parent votes are cast with the vote instruction of the parent proposal.");
+                    Err(ProgramError::InvalidAccountData)
+                }
+                ProposalKind::Standard => {
+                    log!("Error: Standard proposals have nothing to execute.");
+                    Err(ProgramError::InvalidAccountData)
+                }
+            },
+        )
+    })?;
+
+    let counterparty = counterparty(proposal.kind, payload, remaining);
+    for (mint, outflow, inflow) in balances_before.flows(vault, remaining) {
+        for (direction, amount) in [(LedgerDirection::Outflow, outflow), (LedgerDirection::Inflow, inflow)] {
+            if amount == 0 {
+                continue;
+            }
+            if direction == LedgerDirection::Outflow {
+                apply_outflow(multisig_account.key(), vault_index, rate_limit_account, &mint, amount)?;
+            }
+            record_flow(
+                multisig_account.key(),
+                ledger_account,
+                vault_index,
+                direction,
+                LedgerSource::Proposal,
+                &mint,
+                amount,
+                &counterparty,
+                proposal.id,
+            )?;
+        }
+    }
+
+    log!("Proposal {} executed.", proposal.id);
+    Ok(())
+}
+
+/// The account a proposal moves vault funds to or from, as recorded in the ledger
+/// Kinds without a single counterparty (e.g. batches) record the default pubkey
+fn counterparty(kind: ProposalKind, payload: &[u8], remaining: &[AccountInfo]) -> Pubkey {
+    let counterparty = match kind {
+        ProposalKind::Transfer => Transfer::from_bytes(payload).map(|transfer| transfer.recipient).ok(),
+        ProposalKind::CancelStream => CancelStream::from_bytes(payload).map(|cancel| cancel.stream).ok(),
+        ProposalKind::CancelMilestoneEscrow => {
+            CancelMilestoneEscrow::from_bytes(payload).map(|cancel| cancel.escrow).ok()
+        }
+        ProposalKind::WithdrawStake => WithdrawStake::from_bytes(payload).map(|withdraw| withdraw.stake).ok(),
+        // The accounts created by these kinds come first
+        ProposalKind::CreateStream | ProposalKind::CreateMilestoneEscrow | ProposalKind::CreateStake => {
+            remaining.first().map(|account| *account.key())
+        }
+        _ => None,
+    };
+    counterparty.unwrap_or_default()
+}
+
+/// Whether an instruction gives another key control over assets without moving them:
+/// token `Approve`, `SetAuthority` and `ApproveChecked`, system `Assign` and `AssignWithSeed`
+fn grants_control(program: &Pubkey, data: &[u8]) -> bool {
+    if program == &TOKEN_PROGRAM_ID || program == &TOKEN_2022_PROGRAM_ID {
+        return matches!(data.first(), Some(4 | 6 | 13));
+    }
+    if program == &pinocchio_system::ID {
+        return matches!(data.get(..4), Some([1 | 10, 0, 0, 0]));
+    }
+    false
+}
+
+/// Runs every instruction of a batch in order, signed by the vault
+/// `remaining` holds the batch's account keys, then one account per address lookup
+/// table, then every address loaded from those tables, in order
+///
+/// A rate limit only sees what leaves the vault during the batch, so batches of a rate
+/// limited vault cannot approve token delegates, hand over token accounts or assign the
+/// vault to another program (which would let the funds leave later, uncounted)
+pub fn execute_batch(
+    payload: &[u8],
+    vault: &AccountInfo,
+    allowlist: Option<&ProgramAllowlist>,
+    rate_limited: bool,
+    remaining: &[AccountInfo],
+    signer: &Signer,
+) -> ProgramResult {
+    let batch = Batch::parse(payload)?;
+    let static_count = batch.account_keys.len();
+    let table_count = batch.lookup_count as usize;
+
+    if remaining.len() < static_count + table_count + batch.loaded_count
+        || batch
+            .account_keys
+            .iter()
+            .zip(remaining)
+            .any(|(key, account)| key != account.key())
+    {
+        log!("Error: Accounts do not match the batch account keys.");
+        return Err(ProgramError::InvalidArgument);
+    }
+
+    // Accounts in the order instructions index them: static keys, then loaded addresses
+    let mut resolved: Vec<&AccountInfo> = remaining[..static_count].iter().collect();
+    let tables = &remaining[static_count..static_count + table_count];
+    let mut loaded = remaining[static_count + table_count..].iter();
+
+    for (lookup, table) in batch.lookups().zip(tables) {
+        if table.key() != lookup.table || table.owner() != &ADDRESS_LOOKUP_TABLE_PROGRAM_ID {
+            log!("Error: Invalid address lookup table account.");
+            return Err(ProgramError::InvalidArgument);
+        }
+
+        // Verify the loaded accounts against the table's contents on-chain
+        let table_data = table.try_borrow_data()?;
+        let addresses = table_data
+            .get(LOOKUP_TABLE_META_SIZE..)
+            .ok_or(ProgramError::InvalidAccountData)?;
+        for index in lookup.indices {
+            let start = *index as usize * 32;
+            let address = addresses
+                .get(start..start + 32)
+                .ok_or(ProgramError::InvalidArgument)?;
+            let account = loaded.next().ok_or(ProgramError::NotEnoughAccountKeys)?;
+            if account.key().as_ref() != address {
+                log!("Error: Account does not match lookup table entry {}.", *index);
+                return Err(ProgramError::InvalidArgument);
+            }
+            resolved.push(account);
+        }
+    }
+
+    // Rejected before anything runs, so a batch either fully passes the checks or does nothing
+    for instruction in batch.instructions() {
+        let instruction = instruction?;
+        let program = resolved[instruction.program_index as usize].key();
+        if !is_program_allowed(allowlist, program) {
+            log!("Error: Batch invokes a program that is not on the allowlist.");
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        if rate_limited
+            && grants_control(program, instruction.data)
+            && instruction
+                .accounts
+                .iter()
+                .any(|[index, _]| resolved[*index as usize].key() == vault.key())
+        {
+            log!("Error: Batches of a rate limited vault cannot delegate or reassign its assets.");
+            return Err(ProgramError::InvalidArgument);
+        }
+    }
+
+    for (position, instruction) in batch.instructions().enumerate() {
+        let instruction = instruction?;
+
+        let mut metas = Vec::with_capacity(instruction.accounts.len());
+        let mut infos = Vec::with_capacity(instruction.accounts.len());
+        for [index, is_writable] in instruction.accounts {
+            let account = resolved[*index as usize];
+            // The vault is the only account the program can sign for
+            metas.push(AccountMeta::new(account.key(), *is_writable == 1, account.key() == vault.key()));
+            infos.push(account);
+        }
+
+        log!("Executing batch instruction {}", position);
+        slice_invoke_signed(
+            &Instruction {
+                program_id: resolved[instruction.program_index as usize].key(),
+                accounts: &metas,
+                data: instruction.data,
+            },
+            &infos,
+            core::slice::from_ref(signer),
+        )?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,154 @@
+use pinocchio::sysvars::rent::Rent;
+use pinocchio::sysvars::Sysvar;
+use pinocchio::{
+    account_info::AccountInfo,
+    cpi::invoke,
+    instruction::{AccountMeta, Instruction, Seed, Signer},
+    program_error::ProgramError,
+    pubkey::{self, Pubkey},
+    ProgramResult,
+};
+use pinocchio_log::log;
+
+use crate::instructions::VaultBalances;
+use crate::state::{FixedPayload, Guard, GuardHook, LedgerSource, SetGuard};
+use crate::utils::find_program_address;
+
+/// Checks that `guard_account` is the multisig's guard PDA and returns the guard program
+/// Returns `None` if no guard was ever set or it has been removed
+pub fn load_guard(multisig_key: &Pubkey, guard_account: &AccountInfo) -> Result<Option<Pubkey>, ProgramError> {
+    let (pda_guard, _) = find_program_address(&[b"guard", multisig_key.as_ref()], &crate::ID);
+    if &pda_guard != guard_account.key() {
+        log!("Error: Invalid guard PDA.");
+        return Err(ProgramError::InvalidArgument);
+    }
+
+    if guard_account.owner() != &crate::ID {
+        return Ok(None);
+    }
+    let program = Guard::from_account_info(guard_account)?.program;
+    Ok((program != Pubkey::default()).then_some(program))
+}
+
+/// A movement of vault funds the guard is asked to approve
+pub struct GuardCall<'a> {
+    pub source: LedgerSource,       // Instruction moving the funds
+    pub multisig: &'a Pubkey,       // Multisig the vault belongs to
+    pub vault: &'a AccountInfo,     // Vault the funds move in or out of
+    pub vault_index: u8,            // Index of that vault (0 = treasury)
+    pub authority: &'a AccountInfo, // Proposal, spending limit, payment schedule, budget, or module program
+    pub proposal_id: u64,           // Proposal that authorized the movement (0 for modules)
+    pub kind: u8,                   // Kind of the executed proposal (0 for other sources)
+    pub data: &'a [u8],             // Proposal payload, instruction data, or a budget spend's recipient and amount
+}
+
+impl GuardCall<'_> {
+    /// Calls the guard program at `hook`. The guard fails the instruction by returning an error
+    /// Instruction data: hook (u8), source (u8, a `LedgerSource`), multisig (32 bytes), vault
+    /// index (u8), proposal id (u64), proposal kind (u8), vault lamports (u64), token account
+    /// count (u8), then per vault token account its address, mint, and amount (32 + 32 + 8
+    /// bytes), then `data`
+    /// Accounts passed (read-only): vault, authority
+    pub fn invoke(&self, program: &Pubkey, hook: GuardHook, balances: &VaultBalances) -> ProgramResult {
+        let mut data = Vec::with_capacity(53 + balances.tokens.len() * 72 + self.data.len());
+        data.push(hook as u8);
+        data.push(self.source as u8);
+        data.extend_from_slice(self.multisig);
+        data.push(self.vault_index);
+        data.extend_from_slice(&self.proposal_id.to_le_bytes());
+        data.push(self.kind);
+        data.extend_from_slice(&balances.lamports.to_le_bytes());
+        data.push(balances.tokens.len() as u8);
+        for (token_account, mint, amount) in &balances.tokens {
+            data.extend_from_slice(token_account);
+            data.extend_from_slice(mint);
+            data.extend_from_slice(&amount.to_le_bytes());
+        }
+        data.extend_from_slice(self.data);
+
+        invoke(
+            &Instruction {
+                program_id: program,
+                accounts: &[
+                    AccountMeta::readonly(self.vault.key()),
+                    AccountMeta::readonly(self.authority.key()),
+                ],
+                data: &data,
+            },
+            &[self.vault, self.authority],
+        )
+    }
+}
+
+/// Runs `f` between the pre- and post-execution calls to `guard`, if one is set
+/// The vault balances passed to the guard are read from the vault and the token accounts
+/// among `accounts`
+pub fn with_guard(
+    guard: Option<Pubkey>,
+    call: &GuardCall,
+    accounts: &[AccountInfo],
+    f: impl FnOnce() -> ProgramResult,
+) -> ProgramResult {
+    let Some(program) = guard else {
+        return f();
+    };
+
+    call.invoke(&program, GuardHook::PreExecution, &VaultBalances::snapshot(call.vault, accounts))?;
+    f()?;
+    call.invoke(&program, GuardHook::PostExecution, &VaultBalances::snapshot(call.vault, accounts))
+}
+
+/// Executes a `SetGuard` proposal, creating the guard account on first use
+/// Accounts expected (after the common execution accounts, where the guard account is
+/// already passed)
+/// - system program
+pub fn execute_set_guard(
+    payload: &[u8],
+    payer: &AccountInfo,
+    multisig_account: &AccountInfo,
+    guard_account: &AccountInfo,
+) -> ProgramResult {
+    let program = SetGuard::from_bytes(payload)?.program;
+
+    if program == crate::ID {
+        log!("Error: The multisig program cannot be its own guard.");
+        return Err(ProgramError::InvalidArgument);
+    }
+
+    // Checks the guard PDA
+    load_guard(multisig_account.key(), guard_account)?;
+
+    if guard_account.owner() != &crate::ID {
+        log!("Creating guard");
+        let (_, bump) =
+            pubkey::find_program_address(&[b"guard", multisig_account.key().as_ref()], &crate::ID);
+        let bump_seed = [bump];
+        let seeds = [
+            Seed::from(b"guard"),
+            Seed::from(multisig_account.key().as_ref()),
+            Seed::from(&bump_seed),
+        ];
+
+        pinocchio_system::instructions::CreateAccount {
+            from: payer,
+            to: guard_account,
+            lamports: Rent::get()?.minimum_balance(Guard::LEN),
+            space: Guard::LEN as u64,
+            owner: &crate::ID,
+        }
+        .invoke_signed(&[Signer::from(&seeds)])?;
+
+        let guard = Guard::from_account_info(guard_account)?;
+        guard.multisig = *multisig_account.key();
+        guard.bump = bump;
+    }
+
+    Guard::from_account_info(guard_account)?.program = program;
+
+    if program == Pubkey::default() {
+        log!("Removed guard");
+    } else {
+        log!("Set guard");
+    }
+    Ok(())
+}
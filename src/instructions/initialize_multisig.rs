@@ -8,6 +8,7 @@ use pinocchio::{
 use pinocchio_log::log;
 
 use crate::state::Multisig;
+use crate::utils::create_program_address;
 
 pub fn process_initalize_multisig_instructions(
     accounts: &[AccountInfo],
@@ -80,3 +81,24 @@ pub fn process_initalize_multisig_instructions(
 
     Ok(())
 }
+
+/// Loads a multisig, checking it is the `[b"multisig", creator]` PDA it claims to be
+/// Any other account owned by the program (e.g. a transaction buffer, whose bytes its
+/// creator chooses) would otherwise pass for a multisig with whatever members it lists
+pub fn load_multisig(multisig_account: &AccountInfo) -> Result<&mut Multisig, ProgramError> {
+    if multisig_account.owner() != &crate::ID {
+        log!("Error: Multisig must be owned by this program.");
+        return Err(ProgramError::IllegalOwner);
+    }
+    let multisig = Multisig::from_account_info(multisig_account)?;
+
+    let pda_multisig = create_program_address(
+        &[b"multisig", multisig.creator.as_ref(), &[multisig.config_bump]],
+        &crate::ID,
+    )?;
+    if &pda_multisig != multisig_account.key() {
+        log!("Error: Invalid multisig account PDA.");
+        return Err(ProgramError::InvalidArgument);
+    }
+    Ok(multisig)
+}
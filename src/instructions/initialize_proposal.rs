@@ -3,24 +3,90 @@ use pinocchio::sysvars::rent::Rent;
 use pinocchio::sysvars::Sysvar;
 use pinocchio::{
     account_info::AccountInfo,
+    instruction::{Seed, Signer},
     program_error::ProgramError,
     pubkey::{self, Pubkey},
     ProgramResult,
 };
 use pinocchio_log::log;
 
-use crate::state::{Multisig, Proposal, ProposalStatus};
+use crate::instructions::{
+    allowlist_threshold, load_multisig, load_vault_settings, transfer_threshold, unlisted_threshold,
+    vault_address, verify_upgrade_buffer,
+};
+use crate::state::{
+    CreateMilestoneEscrow, CreatePaymentSchedule, CreateStream, FixedPayload, ProgramUpgrade, Proposal,
+    ProposalKind, ProposalPayload, ProposalStatus, Transfer,
+};
+use crate::utils::{create_program_address, sha256};
 
+/// Creates a new proposal for the multisig
+/// Instruction data (`data`) expected
+/// - 8 bytes (optional): Time at which voting opens, in the multisig's `time_unit`. Omitting
+///   it (or passing `0`) opens voting immediately, otherwise the proposal is published for
+///   review until then.
+/// - 1 byte (optional): The proposal kind (see `ProposalKind`). Defaults to Standard.
+/// - 1 byte (optional): Index of the vault the proposal acts on. Defaults to 0 (the treasury).
+/// - Remaining bytes: The payload for the kind (e.g. the serialized batch of instructions)
+///
+/// Accounts expected
+/// - creator, multisig, proposal, system program
+/// - payload account (`[b"payload", proposal]`), only for kinds other than Standard
+/// - vault config (`[b"vault_config", multisig, index]`), only for vaults other than the treasury
+/// - address book (`[b"address_book", multisig]`), only for batches, address book updates
+///   and proposals paying a recipient (transfers, payment schedules, streams, milestone
+///   escrows)
+/// - the buffer to deploy, only for program upgrades (its authority must be the vault)
 pub fn process_initialize_proposal_instruction(
     accounts: &[AccountInfo],
-    _data: &[u8],
+    data: &[u8],
 ) -> ProgramResult {
-    let [creator, multisig_account, proposal_account, _system_program, ..] = accounts else {
+    let [creator, multisig_account, proposal_account, _system_program, remaining @ ..] = accounts else {
         log!("Error: Not enough account keys provided.");
         return Err(ProgramError::NotEnoughAccountKeys);
     };
 
-    let multisig = Multisig::from_account_info(multisig_account)?;
+    let requested_start = match data.get(0..8) {
+        Some(bytes) => u64::from_le_bytes(bytes.try_into().unwrap()),
+        None if data.is_empty() => 0,
+        None => {
+            log!("Error: The voting start must be 8 bytes.");
+            return Err(ProgramError::InvalidInstructionData);
+        }
+    };
+    let kind = match data.get(8) {
+        Some(kind) => ProposalKind::try_from(kind)?,
+        None => ProposalKind::Standard,
+    };
+    let vault_index = data.get(9).copied().unwrap_or(0);
+    let payload = data.get(10..).unwrap_or(&[]);
+
+    create_proposal(
+        creator,
+        multisig_account,
+        proposal_account,
+        remaining,
+        requested_start,
+        kind,
+        vault_index,
+        payload,
+    )
+}
+
+/// Creates and initializes a proposal (and its payload account, if the kind has one)
+/// Shared by the regular instruction and proposals created from a transaction buffer
+#[allow(clippy::too_many_arguments)]
+pub fn create_proposal(
+    creator: &AccountInfo,
+    multisig_account: &AccountInfo,
+    proposal_account: &AccountInfo,
+    remaining: &[AccountInfo],
+    requested_start: u64,
+    kind: ProposalKind,
+    vault_index: u8,
+    payload: &[u8],
+) -> ProgramResult {
+    let multisig = load_multisig(multisig_account)?;
 
     if !multisig.member_keys[..multisig.member_count as usize].contains(creator.key()) {
         log!("Error: Creator is not a member of the multisig.");
@@ -37,13 +103,124 @@ pub fn process_initialize_proposal_instruction(
         multisig_account.key().as_ref(),
         &multisig.total_proposals.to_le_bytes(),
     ];
-    let (pda_proposal, _bump_seed) = pubkey::find_program_address(proposal_seeds, &crate::ID);
+    let (pda_proposal, bump) = pubkey::find_program_address(proposal_seeds, &crate::ID);
 
     if &pda_proposal != proposal_account.key() {
         log!("Error: Invalid proposal account PDA.");
         return Err(ProgramError::InvalidArgument);
     }
 
+    let clock = Clock::get()?;
+    let now = multisig.time_unit.current(&clock);
+    let voting_starts_at = if requested_start == 0 { now } else { requested_start };
+
+    if voting_starts_at < now {
+        log!("Error: Voting cannot be scheduled to start in the past.");
+        return Err(ProgramError::InvalidInstructionData);
+    }
+    // A start far in the future must not wrap the end of voting around to the past
+    let Some(expiration_time) = voting_starts_at.checked_add(multisig.proposal_expiry) else {
+        log!("Error: Voting cannot be scheduled to end that late.");
+        return Err(ProgramError::InvalidInstructionData);
+    };
+
+    if kind.validate_payload(payload).is_err() {
+        log!("Error: Invalid payload for the proposal kind.");
+        return Err(ProgramError::InvalidInstructionData);
+    }
+
+    let mut remaining = remaining.iter();
+    let payload_account = if kind != ProposalKind::Standard {
+        let Some(payload_account) = remaining.next() else {
+            log!("Error: Payload account is required for this proposal kind.");
+            return Err(ProgramError::NotEnoughAccountKeys);
+        };
+        Some(payload_account)
+    } else {
+        None
+    };
+    let vault_config = if vault_index != 0 { remaining.next() } else { None };
+    let (mut threshold, vault_bump) =
+        load_vault_settings(multisig, multisig_account.key(), vault_index, vault_config)?;
+
+    // Changing which programs batches may invoke needs a supermajority
+    if kind == ProposalKind::UpdateProgramAllowlist {
+        threshold = threshold.max(allowlist_threshold(multisig));
+    }
+
+    // The guard, modules and address book reach beyond the proposal's vault, and parent votes
+    // and approved messages speak for the whole multisig, so they take at least the
+    // multisig's own threshold
+    if matches!(
+        kind,
+        ProposalKind::SetGuard
+            | ProposalKind::UpdateModules
+            | ProposalKind::ParentVote
+            | ProposalKind::UpdateAddressBook
+            | ProposalKind::ApproveMessage
+    ) {
+        threshold = threshold.max(multisig.threshold);
+    }
+
+    // Proposals paying out to a recipient are subject to the address book
+    let payment = match kind {
+        ProposalKind::Transfer => {
+            let transfer = Transfer::from_bytes(payload)?;
+            Some((transfer.recipient, transfer.amount))
+        }
+        ProposalKind::CreatePaymentSchedule => {
+            let schedule = CreatePaymentSchedule::from_bytes(payload)?;
+            Some((schedule.recipient, schedule.amount))
+        }
+        ProposalKind::CreateStream => {
+            let stream = CreateStream::from_bytes(payload)?;
+            Some((stream.recipient, stream.amount))
+        }
+        ProposalKind::CreateMilestoneEscrow => {
+            let escrow = CreateMilestoneEscrow::from_bytes(payload)?;
+            let amounts = escrow.amounts;
+            let total = amounts[..(escrow.milestone_count as usize).min(8)]
+                .iter()
+                .fold(0u64, |total, amount| total.saturating_add(*amount));
+            Some((escrow.contractor, total))
+        }
+        _ => None,
+    };
+    if let Some((recipient, amount)) = payment {
+        let Some(book_account) = remaining.next() else {
+            log!("Error: Address book account is required for payment proposals.");
+            return Err(ProgramError::NotEnoughAccountKeys);
+        };
+        threshold = transfer_threshold(multisig_account, book_account, &recipient, amount, threshold)?;
+    }
+
+    // A batch can pay anyone, so the book cannot vouch for its recipients. Changing the book
+    // decides who can be paid without its threshold, so it takes that threshold too
+    if kind == ProposalKind::Batch || kind == ProposalKind::UpdateAddressBook {
+        let Some(book_account) = remaining.next() else {
+            log!("Error: Address book account is required for batch and address book proposals.");
+            return Err(ProgramError::NotEnoughAccountKeys);
+        };
+        threshold = unlisted_threshold(multisig_account, book_account, threshold)?;
+    }
+
+    // Whoever holds the buffer's authority decides the code an upgrade deploys, so it must
+    // be the vault before members vote on it
+    if kind == ProposalKind::ProgramUpgrade {
+        let Some(buffer) = remaining.next() else {
+            log!("Error: Buffer account is required for program upgrade proposals.");
+            return Err(ProgramError::NotEnoughAccountKeys);
+        };
+        let vault = vault_address(multisig_account.key(), vault_index, vault_bump)?;
+        verify_upgrade_buffer(buffer, &ProgramUpgrade::from_bytes(payload)?.buffer, &vault)?;
+    }
+
+    // A threshold of 0 would let a proposal pass without a single vote
+    if threshold == 0 {
+        log!("Error: The multisig has no threshold set. Set one with update_multisig first.");
+        return Err(ProgramError::InvalidArgument);
+    }
+
     log!("Creating new proposal account...");
 
     pinocchio_system::instructions::CreateAccount {
@@ -57,12 +234,30 @@ pub fn process_initialize_proposal_instruction(
 
     log!("Initializing proposal state...");
     let proposal = Proposal::from_account_info(proposal_account)?;
-    let clock = Clock::get()?;
     proposal.creator = *creator.key();
     proposal.id = multisig.total_proposals;
+    proposal.bump = bump;
     proposal.status = ProposalStatus::Active;
+    proposal.kind = kind;
+    // The approval threshold comes from the vault the proposal acts on
+    proposal.vault_index = vault_index;
+    proposal.vault_bump = vault_bump;
+    proposal.threshold = threshold;
+    // Lets integrators check what was approved without reading the payload account
+    proposal.payload_hash = sha256(payload);
     proposal.created_at = clock.unix_timestamp as u64;
-    proposal.expiration_time = proposal.created_at + multisig.proposal_expiry;
+    proposal.time_unit = multisig.time_unit;
+    proposal.multisig = *multisig_account.key();
+    // Deadline rules are fixed at creation, like the time unit they are expressed in
+    proposal.quiet_period = multisig.quiet_period;
+    proposal.quiet_extension = multisig.quiet_extension;
+    proposal.max_extensions = multisig.max_extensions;
+    proposal.extensions_used = 0;
+    proposal.time_lock = multisig.time_lock;
+    proposal.execution_window = multisig.execution_window;
+    proposal.voting_starts_at = voting_starts_at;
+    // The voting window runs from the (possibly scheduled) start, not from creation
+    proposal.expiration_time = expiration_time;
     // Set all votes to 255 (meaning "Not Voted")
     proposal.votes = [255; 20];
     // Copy voters from the multisig members into the proposal's voter list
@@ -72,7 +267,97 @@ pub fn process_initialize_proposal_instruction(
     // Increment the total number of proposals in the parent multisig account
     multisig.total_proposals += 1;
 
+    if let Some(payload_account) = payload_account {
+        create_payload_account(creator, proposal_account, payload_account, payload)?;
+    }
+
     log!("Successfully created proposal with ID: {}", proposal.id);
 
     Ok(())
 }
+
+/// Loads a proposal, checking it is the `[b"proposal", multisig, id]` PDA it claims to be
+/// Any other account owned by the program (e.g. a transaction buffer, whose bytes its
+/// creator chooses) would otherwise pass for a proposal of whatever multisig it names
+pub fn load_proposal(proposal_account: &AccountInfo) -> Result<&mut Proposal, ProgramError> {
+    if proposal_account.owner() != &crate::ID {
+        log!("Error: Proposal must be owned by this program.");
+        return Err(ProgramError::IllegalOwner);
+    }
+    let proposal = Proposal::from_account_info(proposal_account)?;
+
+    let pda_proposal = create_program_address(
+        &[b"proposal", proposal.multisig.as_ref(), &proposal.id.to_le_bytes(), &[proposal.bump]],
+        &crate::ID,
+    )?;
+    if &pda_proposal != proposal_account.key() {
+        log!("Error: Invalid proposal account PDA.");
+        return Err(ProgramError::InvalidArgument);
+    }
+    Ok(proposal)
+}
+
+/// Creates the `[b"payload", proposal]` account and stores the payload bytes in it
+pub fn create_payload_account(
+    payer: &AccountInfo,
+    proposal_account: &AccountInfo,
+    payload_account: &AccountInfo,
+    payload: &[u8],
+) -> ProgramResult {
+    let (pda_payload, bump) = pubkey::find_program_address(
+        &[b"payload", proposal_account.key().as_ref()],
+        &crate::ID,
+    );
+    if &pda_payload != payload_account.key() {
+        log!("Error: Invalid payload account PDA.");
+        return Err(ProgramError::InvalidArgument);
+    }
+
+    let space = ProposalPayload::LEN + payload.len();
+    let bump_seed = [bump];
+    let seeds = [
+        Seed::from(b"payload"),
+        Seed::from(proposal_account.key().as_ref()),
+        Seed::from(&bump_seed),
+    ];
+
+    pinocchio_system::instructions::CreateAccount {
+        from: payer,
+        to: payload_account,
+        lamports: Rent::get()?.minimum_balance(space),
+        space: space as u64,
+        owner: &crate::ID,
+    }
+    .invoke_signed(&[Signer::from(&seeds)])?;
+
+    let header = ProposalPayload::from_account_info(payload_account)?;
+    header.proposal = *proposal_account.key();
+    header.bump = bump;
+    unsafe {
+        payload_account.borrow_mut_data_unchecked()[ProposalPayload::LEN..].copy_from_slice(payload);
+    }
+
+    Ok(())
+}
+
+/// Returns the payload bytes of a proposal, checking the account is its `[b"payload", proposal]` PDA
+pub fn load_payload<'a>(
+    proposal_account: &AccountInfo,
+    payload_account: &'a AccountInfo,
+) -> Result<&'a [u8], ProgramError> {
+    if payload_account.owner() != &crate::ID {
+        log!("Error: Payload must be owned by this program.");
+        return Err(ProgramError::IllegalOwner);
+    }
+    let header = ProposalPayload::from_account_info(payload_account)?;
+
+    let pda_payload = create_program_address(
+        &[b"payload", proposal_account.key().as_ref(), &[header.bump]],
+        &crate::ID,
+    )?;
+    if &pda_payload != payload_account.key() {
+        log!("Error: Payload account does not belong to this proposal.");
+        return Err(ProgramError::InvalidArgument);
+    }
+    Ok(ProposalPayload::data(payload_account))
+}
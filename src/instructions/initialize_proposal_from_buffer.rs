@@ -0,0 +1,67 @@
+use pinocchio::{account_info::AccountInfo, program_error::ProgramError, ProgramResult};
+use pinocchio_log::log;
+
+use crate::instructions::{close_buffer, create_proposal, load_buffer};
+use crate::state::ProposalKind;
+use crate::utils::sha256;
+
+/// Creates a proposal whose payload is the content of a completed transaction buffer
+/// The buffer must be fully uploaded and match its declared hash. It is closed
+/// afterwards and its rent refunded to the creator.
+/// Instruction data (`data`) expected
+/// - 8 bytes: Time at which voting opens (`0` opens voting immediately)
+/// - 1 byte: The proposal kind the buffer holds a payload for
+/// - 1 byte: Index of the vault the proposal acts on (0 = the treasury)
+///
+/// Accounts expected
+/// - creator, multisig, proposal, system program, buffer, payload account
+/// - vault config (`[b"vault_config", multisig, index]`), only for vaults other than the treasury
+/// - address book (`[b"address_book", multisig]`), only for batches and proposals paying a
+///   recipient
+pub fn process_initialize_proposal_from_buffer_instruction(
+    accounts: &[AccountInfo],
+    data: &[u8],
+) -> ProgramResult {
+    let [creator, multisig_account, proposal_account, _system_program, buffer_account, remaining @ ..] = accounts else {
+        log!("Error: Not enough accounts provided. Expected 6.");
+        return Err(ProgramError::NotEnoughAccountKeys);
+    };
+
+    if !creator.is_signer() {
+        log!("Error: The creator must sign the transaction.");
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+
+    if data.len() < 10 {
+        log!("Error: Instruction data is invalid. Expected 10 bytes.");
+        return Err(ProgramError::InvalidInstructionData);
+    }
+    let requested_start = u64::from_le_bytes(data[0..8].try_into().unwrap());
+    let kind = ProposalKind::try_from(&data[8])?;
+    let vault_index = data[9];
+
+    let buffer = load_buffer(creator, multisig_account, buffer_account)?;
+    if !buffer.is_complete() {
+        log!("Error: Buffer is not complete ({} / {} bytes).", buffer.current_size, buffer.final_size);
+        return Err(ProgramError::InvalidAccountData);
+    }
+
+    let payload = buffer.data(buffer_account);
+    if sha256(payload) != buffer.final_hash {
+        log!("Error: Buffer content does not match its declared hash.");
+        return Err(ProgramError::InvalidAccountData);
+    }
+
+    create_proposal(
+        creator,
+        multisig_account,
+        proposal_account,
+        remaining,
+        requested_start,
+        kind,
+        vault_index,
+        payload,
+    )?;
+
+    close_buffer(creator, buffer_account)
+}
@@ -0,0 +1,111 @@
+use pinocchio::sysvars::clock::Clock;
+use pinocchio::sysvars::rent::Rent;
+use pinocchio::sysvars::Sysvar;
+use pinocchio::{
+    account_info::AccountInfo,
+    instruction::{Seed, Signer},
+    program_error::ProgramError,
+    pubkey::{self, Pubkey},
+    ProgramResult,
+};
+use pinocchio_log::log;
+
+use crate::instructions::load_multisig;
+use crate::state::{Ledger, LedgerDirection, LedgerEntry, LedgerSource};
+use crate::utils::{create_program_address, find_program_address};
+
+/// Creates the ledger of a multisig. Anyone can pay for it; from then on every
+/// instruction moving vault funds records to it
+/// Accounts expected
+/// - payer (signer), multisig, ledger (`[b"ledger", multisig]`), system program
+pub fn process_initialize_ledger_instruction(accounts: &[AccountInfo], _data: &[u8]) -> ProgramResult {
+    let [payer, multisig_account, ledger_account, _system_program, ..] = accounts else {
+        log!("Error: Not enough accounts provided. Expected 4.");
+        return Err(ProgramError::NotEnoughAccountKeys);
+    };
+
+    load_multisig(multisig_account)?;
+    if ledger_account.owner() == &crate::ID {
+        log!("Error: Ledger account is already initialized.");
+        return Err(ProgramError::AccountAlreadyInitialized);
+    }
+
+    let (pda_ledger, bump) = pubkey::find_program_address(
+        &[b"ledger", multisig_account.key().as_ref()],
+        &crate::ID,
+    );
+    if &pda_ledger != ledger_account.key() {
+        log!("Error: Invalid ledger PDA.");
+        return Err(ProgramError::InvalidArgument);
+    }
+
+    let bump_seed = [bump];
+    let seeds = [
+        Seed::from(b"ledger"),
+        Seed::from(multisig_account.key().as_ref()),
+        Seed::from(&bump_seed),
+    ];
+
+    pinocchio_system::instructions::CreateAccount {
+        from: payer,
+        to: ledger_account,
+        lamports: Rent::get()?.minimum_balance(Ledger::LEN),
+        space: Ledger::LEN as u64,
+        owner: &crate::ID,
+    }
+    .invoke_signed(&[Signer::from(&seeds)])?;
+
+    let ledger = Ledger::from_account_info(ledger_account)?;
+    ledger.multisig = *multisig_account.key();
+    ledger.entry_count = 0;
+    ledger.bump = bump;
+
+    log!("Created ledger");
+    Ok(())
+}
+
+/// Appends a flow of `amount` of `asset` through a vault to the multisig's ledger
+/// Does nothing if the ledger has not been created
+#[allow(clippy::too_many_arguments)]
+pub fn record_flow(
+    multisig_key: &Pubkey,
+    ledger_account: &AccountInfo,
+    vault_index: u8,
+    direction: LedgerDirection,
+    source: LedgerSource,
+    asset: &Pubkey,
+    amount: u64,
+    counterparty: &Pubkey,
+    proposal_id: u64,
+) -> ProgramResult {
+    // Once created the ledger holds its bump, so only multisigs without a ledger pay for
+    // the bump search (and still cannot skip recording by passing another account)
+    if ledger_account.owner() != &crate::ID {
+        let (pda_ledger, _) = find_program_address(&[b"ledger", multisig_key.as_ref()], &crate::ID);
+        if &pda_ledger != ledger_account.key() {
+            log!("Error: Invalid ledger PDA.");
+            return Err(ProgramError::InvalidArgument);
+        }
+        return Ok(());
+    }
+
+    let ledger = Ledger::from_account_info(ledger_account)?;
+    let pda_ledger = create_program_address(&[b"ledger", multisig_key.as_ref(), &[ledger.bump]], &crate::ID)?;
+    if &pda_ledger != ledger_account.key() {
+        log!("Error: Invalid ledger PDA.");
+        return Err(ProgramError::InvalidArgument);
+    }
+
+    ledger.append(LedgerEntry {
+        asset: *asset,
+        counterparty: *counterparty,
+        amount,
+        proposal_id,
+        timestamp: Clock::get()?.unix_timestamp as u64,
+        vault_index,
+        direction,
+        source,
+        reserved: [0; 5],
+    });
+    Ok(())
+}
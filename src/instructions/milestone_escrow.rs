@@ -0,0 +1,438 @@
+use pinocchio::sysvars::clock::Clock;
+use pinocchio::sysvars::rent::Rent;
+use pinocchio::sysvars::Sysvar;
+use pinocchio::{
+    account_info::AccountInfo,
+    instruction::{Seed, Signer},
+    program_error::ProgramError,
+    pubkey::{self, Pubkey},
+    ProgramResult,
+};
+use pinocchio_log::log;
+
+use crate::instructions::{require_program_allowed, verify_destination};
+use crate::state::{
+    CancelMilestoneEscrow, CreateMilestoneEscrow, FixedPayload, Milestone, MilestoneEscrow, Multisig,
+    ProgramAllowlist, Proposal,
+};
+use crate::utils::{create_program_address, pay_from_escrow, transfer_sol, transfer_token};
+
+/// Executes a `CreateMilestoneEscrow` proposal, escrowing the total of the milestones
+/// from the proposal's vault. The members and threshold of the proposal are kept
+/// to approve milestones.
+/// Accounts expected (after the common execution accounts)
+/// - milestone escrow (`[b"milestone_escrow", proposal]`), system program
+/// - escrow token account (owned by the milestone escrow), token program, and vault
+///   token account holding the mint (tokens only)
+#[allow(clippy::too_many_arguments)]
+pub fn execute_create_milestone_escrow(
+    payload: &[u8],
+    payer: &AccountInfo,
+    multisig: &Multisig,
+    vault: &AccountInfo,
+    proposal_account: &AccountInfo,
+    proposal: &Proposal,
+    allowlist: Option<&ProgramAllowlist>,
+    remaining: &[AccountInfo],
+    signer: &Signer,
+) -> ProgramResult {
+    let create_escrow = CreateMilestoneEscrow::from_bytes(payload)?;
+    let mint = create_escrow.mint;
+    let milestone_count = create_escrow.milestone_count as usize;
+    let amounts = create_escrow.amounts;
+
+    let [escrow_account, _system_program, remaining @ ..] = remaining else {
+        log!("Error: Not enough accounts provided to create the milestone escrow.");
+        return Err(ProgramError::NotEnoughAccountKeys);
+    };
+
+    if milestone_count == 0 || milestone_count > 8 || amounts[..milestone_count].contains(&0) {
+        log!("Error: An escrow needs between 1 and 8 milestones, each with an amount.");
+        return Err(ProgramError::InvalidInstructionData);
+    }
+    let total = amounts[..milestone_count]
+        .iter()
+        .try_fold(0u64, |total, amount| total.checked_add(*amount))
+        .ok_or(ProgramError::InvalidInstructionData)?;
+
+    let (pda_escrow, bump) = pubkey::find_program_address(
+        &[b"milestone_escrow", proposal_account.key().as_ref()],
+        &crate::ID,
+    );
+    if &pda_escrow != escrow_account.key() {
+        log!("Error: Invalid milestone escrow PDA.");
+        return Err(ProgramError::InvalidArgument);
+    }
+
+    let bump_seed = [bump];
+    let seeds = [
+        Seed::from(b"milestone_escrow"),
+        Seed::from(proposal_account.key().as_ref()),
+        Seed::from(&bump_seed),
+    ];
+
+    pinocchio_system::instructions::CreateAccount {
+        from: payer,
+        to: escrow_account,
+        lamports: Rent::get()?.minimum_balance(MilestoneEscrow::LEN),
+        space: MilestoneEscrow::LEN as u64,
+        owner: &crate::ID,
+    }
+    .invoke_signed(&[Signer::from(&seeds)])?;
+
+    let escrow = MilestoneEscrow::from_account_info(escrow_account)?;
+    escrow.proposal = *proposal_account.key();
+    escrow.multisig = proposal.multisig;
+    escrow.mint = mint;
+    escrow.contractor = create_escrow.contractor;
+    // Milestones are approved by the members at the time of creation
+    let member_count = multisig.member_count as usize;
+    escrow.members = [Pubkey::default(); 10];
+    escrow.members[..member_count].copy_from_slice(&multisig.member_keys[..member_count]);
+    escrow.member_count = multisig.member_count;
+    escrow.threshold = proposal.threshold;
+    escrow.milestones = [Milestone::default(); 8];
+    for (milestone, amount) in escrow.milestones.iter_mut().zip(&amounts[..milestone_count]) {
+        milestone.amount = *amount;
+    }
+    escrow.milestone_count = create_escrow.milestone_count;
+    escrow.deadline = create_escrow.deadline;
+    escrow.cancelled = 0;
+    escrow.vault_index = proposal.vault_index;
+    escrow.vault_bump = proposal.vault_bump;
+    escrow.bump = bump;
+
+    if mint == Pubkey::default() {
+        escrow.token_escrow = Pubkey::default();
+        require_program_allowed(allowlist, &pinocchio_system::ID)?;
+        log!("Escrowing {} lamports over {} milestones", total, milestone_count);
+        return transfer_sol(vault, escrow_account, total, signer);
+    }
+
+    let [token_escrow, token_program, source, ..] = remaining else {
+        log!("Error: Escrow and vault token accounts are required for token escrows.");
+        return Err(ProgramError::NotEnoughAccountKeys);
+    };
+    verify_destination(token_escrow, token_program, &mint, escrow_account.key())?;
+    require_program_allowed(allowlist, token_program.key())?;
+    escrow.token_escrow = *token_escrow.key();
+
+    log!("Escrowing {} tokens over {} milestones", total, milestone_count);
+    transfer_token(source, token_escrow, vault, &mint, token_program, total, signer)
+}
+
+/// Executes a `CancelMilestoneEscrow` proposal once the escrow's deadline has passed,
+/// returning every unreleased milestone to the proposal's vault
+/// Accounts expected (after the common execution accounts)
+/// - milestone escrow, destination (the vault for SOL, a vault token account for tokens),
+///   system or token program
+/// - escrow token account (tokens only)
+pub fn execute_cancel_milestone_escrow(
+    payload: &[u8],
+    multisig_account: &AccountInfo,
+    vault: &AccountInfo,
+    proposal: &Proposal,
+    remaining: &[AccountInfo],
+    clock: &Clock,
+) -> ProgramResult {
+    let escrow_key = CancelMilestoneEscrow::from_bytes(payload)?.escrow;
+
+    let [escrow_account, destination, program, remaining @ ..] = remaining else {
+        log!("Error: Not enough accounts provided to cancel the milestone escrow.");
+        return Err(ProgramError::NotEnoughAccountKeys);
+    };
+
+    if escrow_account.key() != &escrow_key {
+        log!("Error: Milestone escrow account does not match the proposal.");
+        return Err(ProgramError::InvalidArgument);
+    }
+    let escrow = load_milestone_escrow(escrow_account)?;
+    if escrow.multisig != *multisig_account.key() || escrow.vault_index != proposal.vault_index {
+        log!("Error: Milestone escrow was not funded by this vault.");
+        return Err(ProgramError::InvalidArgument);
+    }
+    if escrow.cancelled == 1 {
+        log!("Error: Milestone escrow is already cancelled.");
+        return Err(ProgramError::InvalidAccountData);
+    }
+    if (clock.unix_timestamp as u64) <= escrow.deadline {
+        log!("Error: Funds cannot be clawed back before the deadline {}.", escrow.deadline);
+        return Err(ProgramError::InvalidArgument);
+    }
+    verify_destination(destination, program, &escrow.mint, vault.key())?;
+
+    let unreleased = escrow.unreleased();
+    escrow.cancelled = 1;
+
+    log!("Clawing back {} from the milestone escrow", unreleased);
+    pay_out(escrow_account, escrow, destination, program, remaining, unreleased)
+}
+
+/// Approves a milestone of an escrow, releasing its payment to the contractor once
+/// the escrow's threshold of members have approved it
+/// Instruction data (`data`) expected
+/// - 1 byte: The index of the milestone
+///
+/// Accounts expected
+/// - member (signer, member at the escrow's creation), milestone escrow, destination
+///   (the contractor for SOL, a token account owned by the contractor for tokens),
+///   system or token program
+/// - escrow token account (tokens only)
+pub fn process_approve_milestone_instruction(accounts: &[AccountInfo], data: &[u8]) -> ProgramResult {
+    approve_milestone(accounts, data, &Clock::get()?)
+}
+
+fn approve_milestone(accounts: &[AccountInfo], data: &[u8], clock: &Clock) -> ProgramResult {
+    let [member, escrow_account, destination, program, remaining @ ..] = accounts else {
+        log!("Error: Not enough accounts provided. Expected at least 4.");
+        return Err(ProgramError::NotEnoughAccountKeys);
+    };
+
+    if !member.is_signer() {
+        log!("Error: The member must sign the transaction.");
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+
+    let Some(&index) = data.first() else {
+        log!("Error: Instruction data is invalid. Expected the milestone index.");
+        return Err(ProgramError::InvalidInstructionData);
+    };
+
+    let escrow = load_milestone_escrow(escrow_account)?;
+
+    let Some(position) = escrow.members[..escrow.member_count as usize]
+        .iter()
+        .position(|key| key == member.key())
+    else {
+        log!("Error: Signer cannot approve milestones of this escrow.");
+        return Err(ProgramError::IllegalOwner);
+    };
+    if escrow.cancelled == 1 {
+        log!("Error: Milestone escrow has been cancelled.");
+        return Err(ProgramError::InvalidAccountData);
+    }
+    if index >= escrow.milestone_count {
+        log!("Error: Milestone {} does not exist.", index);
+        return Err(ProgramError::InvalidInstructionData);
+    }
+    verify_destination(destination, program, &escrow.mint, &escrow.contractor)?;
+
+    let threshold = escrow.threshold;
+    let milestone = &mut escrow.milestones[index as usize];
+    if milestone.released_at != 0 {
+        log!("Error: Milestone {} has already been released.", index);
+        return Err(ProgramError::InvalidAccountData);
+    }
+    if milestone.approvals & (1 << position) != 0 {
+        log!("Error: Member has already approved milestone {}.", index);
+        return Err(ProgramError::InvalidArgument);
+    }
+    milestone.approvals |= 1 << position;
+
+    let approvals = milestone.approvals.count_ones() as u64;
+    if approvals < threshold {
+        log!("Milestone {} approved ({} of {})", index, approvals, threshold);
+        return Ok(());
+    }
+
+    milestone.released_at = clock.unix_timestamp as u64;
+    let amount = milestone.amount;
+
+    log!("Milestone {} released: {}", index, amount);
+    pay_out(escrow_account, escrow, destination, program, remaining, amount)
+}
+
+/// Loads a milestone escrow, checking it is the `[b"milestone_escrow", proposal]` PDA it
+/// claims to be
+pub fn load_milestone_escrow(
+    escrow_account: &AccountInfo,
+) -> Result<&mut MilestoneEscrow, ProgramError> {
+    if escrow_account.owner() != &crate::ID {
+        log!("Error: Milestone escrow must be owned by this program.");
+        return Err(ProgramError::IllegalOwner);
+    }
+    let escrow = MilestoneEscrow::from_account_info(escrow_account)?;
+
+    let pda_escrow = create_program_address(
+        &[b"milestone_escrow", escrow.proposal.as_ref(), &[escrow.bump]],
+        &crate::ID,
+    )?;
+    if &pda_escrow != escrow_account.key() {
+        log!("Error: Invalid milestone escrow PDA.");
+        return Err(ProgramError::InvalidArgument);
+    }
+    Ok(escrow)
+}
+
+/// Moves `amount` out of a milestone escrow, signed by the escrow PDA
+fn pay_out(
+    escrow_account: &AccountInfo,
+    escrow: &MilestoneEscrow,
+    destination: &AccountInfo,
+    program: &AccountInfo,
+    remaining: &[AccountInfo],
+    amount: u64,
+) -> ProgramResult {
+    let token_escrow = remaining.first();
+    if escrow.mint != Pubkey::default()
+        && token_escrow.is_some_and(|token_escrow| token_escrow.key() != &escrow.token_escrow)
+    {
+        log!("Error: Escrow token account does not match the milestone escrow.");
+        return Err(ProgramError::InvalidArgument);
+    }
+
+    let bump_seed = [escrow.bump];
+    let seeds = [
+        Seed::from(b"milestone_escrow"),
+        Seed::from(escrow.proposal.as_ref()),
+        Seed::from(&bump_seed),
+    ];
+    pay_from_escrow(escrow_account, token_escrow, destination, program, &escrow.mint, amount, &Signer::from(&seeds))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::ProposalStatus;
+    use crate::test_utils::{program_address, proposal_account, TestAccount};
+
+    const MULTISIG: Pubkey = [0x11; 32];
+    const PROPOSAL: Pubkey = [0x12; 32];
+    const CONTRACTOR: Pubkey = [0x13; 32];
+    const VAULT: Pubkey = [0x14; 32];
+    const MEMBERS: [Pubkey; 3] = [[0x21; 32], [0x22; 32], [0x23; 32]];
+    const DEADLINE: i64 = 10_000;
+
+    /// A SOL escrow of 100 then 200 lamports, released by 2 of `MEMBERS`
+    fn escrow_account() -> TestAccount {
+        let key = program_address(&[b"milestone_escrow", &PROPOSAL, &[255]]);
+        let mut account = TestAccount::new(key, crate::ID, MilestoneEscrow::LEN).with_lamports(1_300);
+        let escrow = account.state::<MilestoneEscrow>();
+        escrow.proposal = PROPOSAL;
+        escrow.multisig = MULTISIG;
+        escrow.contractor = CONTRACTOR;
+        escrow.members[..3].copy_from_slice(&MEMBERS);
+        escrow.member_count = 3;
+        escrow.threshold = 2;
+        escrow.milestones[0].amount = 100;
+        escrow.milestones[1].amount = 200;
+        escrow.milestone_count = 2;
+        escrow.deadline = DEADLINE as u64;
+        escrow.bump = 255;
+        account
+    }
+
+    fn clock(unix_timestamp: i64) -> Clock {
+        Clock { unix_timestamp, ..Clock::default() }
+    }
+
+    /// Approves `index` of `escrow` as `member`, paying to the contractor's account
+    fn approve(
+        escrow: &mut TestAccount,
+        contractor: &mut TestAccount,
+        member: Pubkey,
+        index: u8,
+    ) -> ProgramResult {
+        let mut member = TestAccount::new(member, pinocchio_system::ID, 0).signer();
+        let mut program = TestAccount::new(pinocchio_system::ID, [0; 32], 0);
+        let accounts = [member.info(), escrow.info(), contractor.info(), program.info()];
+        approve_milestone(&accounts, &[index], &clock(5_000))
+    }
+
+    fn cancel(escrow: &mut TestAccount, vault: &mut TestAccount, now: i64) -> ProgramResult {
+        let mut multisig = TestAccount::new(MULTISIG, crate::ID, 0);
+        let mut program = TestAccount::new(pinocchio_system::ID, [0; 32], 0);
+        let mut proposal = proposal_account(&MULTISIG, 1, ProposalStatus::Succeeded);
+        let escrow_key = *escrow.info().key();
+        let remaining = [escrow.info(), vault.info(), program.info()];
+        execute_cancel_milestone_escrow(
+            &escrow_key,
+            &multisig.info(),
+            &vault.info(),
+            proposal.state::<Proposal>(),
+            &remaining,
+            &clock(now),
+        )
+    }
+
+    #[test]
+    fn a_milestone_is_released_once_the_threshold_approves() {
+        let mut escrow = escrow_account();
+        let mut contractor = TestAccount::new(CONTRACTOR, pinocchio_system::ID, 0);
+
+        assert_eq!(approve(&mut escrow, &mut contractor, MEMBERS[0], 0), Ok(()));
+        assert_eq!(contractor.info().lamports(), 0);
+        assert_eq!(
+            approve(&mut escrow, &mut contractor, MEMBERS[0], 0),
+            Err(ProgramError::InvalidArgument)
+        );
+
+        assert_eq!(approve(&mut escrow, &mut contractor, MEMBERS[2], 0), Ok(()));
+        assert_eq!(contractor.info().lamports(), 100);
+        assert_eq!(escrow.info().lamports(), 1_200);
+        assert_eq!(escrow.state::<MilestoneEscrow>().milestones[0].released_at, 5_000);
+        assert_eq!(
+            approve(&mut escrow, &mut contractor, MEMBERS[1], 0),
+            Err(ProgramError::InvalidAccountData)
+        );
+    }
+
+    #[test]
+    fn milestones_are_released_one_by_one() {
+        let mut escrow = escrow_account();
+        let mut contractor = TestAccount::new(CONTRACTOR, pinocchio_system::ID, 0);
+
+        approve(&mut escrow, &mut contractor, MEMBERS[0], 1).unwrap();
+        approve(&mut escrow, &mut contractor, MEMBERS[1], 1).unwrap();
+        assert_eq!(contractor.info().lamports(), 200);
+        assert_eq!(escrow.state::<MilestoneEscrow>().milestones[0].released_at, 0);
+        assert_eq!(escrow.state::<MilestoneEscrow>().unreleased(), 100);
+
+        assert_eq!(
+            approve(&mut escrow, &mut contractor, [0x24; 32], 0),
+            Err(ProgramError::IllegalOwner)
+        );
+        assert_eq!(
+            approve(&mut escrow, &mut contractor, MEMBERS[0], 2),
+            Err(ProgramError::InvalidInstructionData)
+        );
+    }
+
+    #[test]
+    fn cancelling_returns_only_unreleased_milestones_after_the_deadline() {
+        let mut escrow = escrow_account();
+        let mut contractor = TestAccount::new(CONTRACTOR, pinocchio_system::ID, 0);
+        let mut vault = TestAccount::new(VAULT, pinocchio_system::ID, 0);
+        approve(&mut escrow, &mut contractor, MEMBERS[0], 0).unwrap();
+        approve(&mut escrow, &mut contractor, MEMBERS[1], 0).unwrap();
+
+        assert_eq!(cancel(&mut escrow, &mut vault, DEADLINE), Err(ProgramError::InvalidArgument));
+        assert_eq!(cancel(&mut escrow, &mut vault, DEADLINE + 1), Ok(()));
+        assert_eq!(vault.info().lamports(), 200);
+        assert_eq!(escrow.info().lamports(), 1_000);
+
+        assert_eq!(cancel(&mut escrow, &mut vault, DEADLINE + 1), Err(ProgramError::InvalidAccountData));
+        assert_eq!(
+            approve(&mut escrow, &mut contractor, MEMBERS[0], 1),
+            Err(ProgramError::InvalidAccountData)
+        );
+    }
+
+    #[test]
+    fn forged_escrows_are_rejected() {
+        // Owned by the program with the escrow's contents, but not at its PDA
+        let mut escrow = escrow_account();
+        let mut forged = TestAccount::new([0x15; 32], crate::ID, MilestoneEscrow::LEN);
+        let contents = unsafe { escrow.info().borrow_data_unchecked().to_vec() };
+        unsafe { forged.info().borrow_mut_data_unchecked().copy_from_slice(&contents) };
+        let mut contractor = TestAccount::new(CONTRACTOR, pinocchio_system::ID, 0);
+        let mut vault = TestAccount::new(VAULT, pinocchio_system::ID, 0);
+
+        assert_eq!(cancel(&mut forged, &mut vault, DEADLINE + 1), Err(ProgramError::InvalidArgument));
+        assert_eq!(
+            approve(&mut forged, &mut contractor, MEMBERS[0], 0),
+            Err(ProgramError::InvalidArgument)
+        );
+        assert_eq!(vault.info().lamports(), 0);
+    }
+}
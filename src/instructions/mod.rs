@@ -5,12 +5,52 @@ pub mod initialize_proposal;
 pub mod update_multisig;
 pub mod close_proposal;
 pub mod vote_proposal;
+pub mod execute_proposal;
+pub mod transaction_buffer;
+pub mod initialize_proposal_from_buffer;
+pub mod program_upgrade;
+pub mod vault;
+pub mod spending_limit;
+pub mod address_book;
+pub mod transfer;
+pub mod rate_limit;
+pub mod payment_schedule;
+pub mod stream;
+pub mod milestone_escrow;
+pub mod budget;
+pub mod ledger;
+pub mod stake;
+pub mod signed_message;
+pub mod verify_proposal;
+pub mod program_allowlist;
+pub mod guard;
+pub mod module;
 
 pub use initialize_multisig::*;
 pub use initialize_proposal::*;
 pub use update_multisig::*;
 pub use vote_proposal::*;
 pub use close_proposal::*;
+pub use execute_proposal::*;
+pub use transaction_buffer::*;
+pub use initialize_proposal_from_buffer::*;
+pub use program_upgrade::*;
+pub use vault::*;
+pub use spending_limit::*;
+pub use address_book::*;
+pub use transfer::*;
+pub use rate_limit::*;
+pub use payment_schedule::*;
+pub use stream::*;
+pub use milestone_escrow::*;
+pub use budget::*;
+pub use ledger::*;
+pub use stake::*;
+pub use signed_message::*;
+pub use verify_proposal::*;
+pub use program_allowlist::*;
+pub use guard::*;
+pub use module::*;
 
 pub enum MultisigInstructions {
     InitializeMultisig = 0,
@@ -18,6 +58,17 @@ pub enum MultisigInstructions {
     UpdateMultisig = 2,
     Vote = 3,
     CloseProposal = 4,
+    ExecuteProposal = 5,
+    TransactionBuffer = 6,
+    InitializeProposalFromBuffer = 7,
+    UseSpendingLimit = 8,
+    CrankPaymentSchedule = 9,
+    ClaimStream = 10,
+    ApproveMilestone = 11,
+    SpendBudget = 12,
+    InitializeLedger = 13,
+    VerifyProposal = 14,
+    ExecuteModule = 15,
 }
 
 
@@ -31,6 +82,17 @@ impl TryFrom<&u8> for MultisigInstructions {
             2 => Ok(MultisigInstructions::UpdateMultisig),
             3 => Ok(MultisigInstructions::Vote),
             4 => Ok(MultisigInstructions::CloseProposal),
+            5 => Ok(MultisigInstructions::ExecuteProposal),
+            6 => Ok(MultisigInstructions::TransactionBuffer),
+            7 => Ok(MultisigInstructions::InitializeProposalFromBuffer),
+            8 => Ok(MultisigInstructions::UseSpendingLimit),
+            9 => Ok(MultisigInstructions::CrankPaymentSchedule),
+            10 => Ok(MultisigInstructions::ClaimStream),
+            11 => Ok(MultisigInstructions::ApproveMilestone),
+            12 => Ok(MultisigInstructions::SpendBudget),
+            13 => Ok(MultisigInstructions::InitializeLedger),
+            14 => Ok(MultisigInstructions::VerifyProposal),
+            15 => Ok(MultisigInstructions::ExecuteModule),
             _ => Err(ProgramError::InvalidInstructionData)
         }
     }
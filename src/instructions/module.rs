@@ -0,0 +1,220 @@
+use pinocchio::{
+    account_info::AccountInfo,
+    program_error::ProgramError,
+    pubkey::{self, Pubkey},
+    ProgramResult,
+};
+use pinocchio_log::log;
+
+use crate::instructions::{
+    apply_outflow, execute_batch, load_guard, load_multisig, load_program_allowlist, load_rate_limit,
+    record_flow, verify_vault, with_guard, with_vault_signer, GuardCall, VaultBalances,
+};
+use crate::state::{FixedPayload, LedgerDirection, LedgerSource, Multisig, UpdateModules};
+
+/// Executes an `UpdateModules` proposal, registering or unregistering a module program
+pub fn execute_update_modules(payload: &[u8], multisig: &mut Multisig) -> ProgramResult {
+    let update = UpdateModules::from_bytes(payload)?;
+    let program = update.program;
+    let count = multisig.module_count as usize;
+    let position = multisig.modules[..count].iter().position(|module| module == &program);
+
+    match (update.action, position) {
+        (0, Some(_)) => {
+            log!("Error: Module is already registered.");
+            Err(ProgramError::InvalidArgument)
+        }
+        (0, None) => {
+            if program == crate::ID || program == Pubkey::default() {
+                log!("Error: Invalid module program.");
+                return Err(ProgramError::InvalidArgument);
+            }
+            if count == Multisig::MAX_MODULES {
+                log!("Error: The module registry is full.");
+                return Err(ProgramError::InvalidArgument);
+            }
+            multisig.modules[count] = program;
+            multisig.module_count += 1;
+            log!("Registered module");
+            Ok(())
+        }
+        (1, Some(index)) => {
+            // Swap in the last entry to keep the registry contiguous
+            multisig.modules[index] = multisig.modules[count - 1];
+            multisig.modules[count - 1] = Pubkey::default();
+            multisig.module_count -= 1;
+            log!("Unregistered module");
+            Ok(())
+        }
+        (1, None) => {
+            log!("Error: Module is not registered.");
+            Err(ProgramError::InvalidArgument)
+        }
+        _ => {
+            log!("Error: Invalid module registry action.");
+            Err(ProgramError::InvalidInstructionData)
+        }
+    }
+}
+
+/// Lets a registered module run a batch with the treasury as signer, without a vote
+/// The module proves itself by signing with its PDA `[b"module", multisig]` (derived from
+/// the module program). The batch is held to the same constraints as proposal batches: only
+/// allowlisted programs, the treasury's rate limit, the multisig's guard, and every flow
+/// recorded to the ledger
+/// Accounts expected
+/// - module authority (signer), module program, multisig, treasury, the treasury's rate
+///   limit (`[b"rate_limit", multisig, 0]`, even if none is set), the multisig's ledger
+///   (`[b"ledger", multisig]`, even if not created), the multisig's guard (`[b"guard",
+///   multisig]`, even if not set)
+/// - the multisig's program allowlist (`[b"program_allowlist", multisig]`, even if not
+///   created), then the batch accounts (see `execute_batch`)
+/// - the guard program, if one is set
+///
+/// Instruction data: the batch, serialized as in a batch proposal's payload
+pub fn process_execute_module_instruction(accounts: &[AccountInfo], data: &[u8]) -> ProgramResult {
+    let [module_authority, module_program, multisig_account, treasury, rate_limit_account, ledger_account, guard_account, remaining @ ..] =
+        accounts
+    else {
+        log!("Error: Not enough accounts provided. Expected at least 7.");
+        return Err(ProgramError::NotEnoughAccountKeys);
+    };
+
+    let multisig = load_multisig(multisig_account)?;
+
+    if !multisig.modules[..multisig.module_count as usize].contains(module_program.key()) {
+        log!("Error: Program is not a registered module of the multisig.");
+        return Err(ProgramError::IllegalOwner);
+    }
+
+    let (pda_authority, _) =
+        pubkey::find_program_address(&[b"module", multisig_account.key().as_ref()], module_program.key());
+    if &pda_authority != module_authority.key() || !module_authority.is_signer() {
+        log!("Error: The module authority PDA must sign the transaction.");
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+
+    verify_vault(multisig_account.key(), 0, multisig.treasury_bump, treasury)?;
+
+    let rate_limited = load_rate_limit(multisig_account.key(), 0, rate_limit_account)?
+        .is_some_and(|rate_limit| rate_limit.limit_count > 0);
+    let [allowlist_account, batch_accounts @ ..] = remaining else {
+        log!("Error: Program allowlist account is required for module batches.");
+        return Err(ProgramError::NotEnoughAccountKeys);
+    };
+    let allowlist = load_program_allowlist(multisig_account.key(), allowlist_account)?
+        .map(|allowlist| &*allowlist);
+    let balances_before = VaultBalances::snapshot(treasury, remaining);
+
+    let guard = load_guard(multisig_account.key(), guard_account)?;
+    let guard_call = GuardCall {
+        source: LedgerSource::Module,
+        multisig: multisig_account.key(),
+        vault: treasury,
+        vault_index: 0,
+        authority: module_program,
+        proposal_id: 0,
+        kind: 0,
+        data,
+    };
+
+    with_guard(guard, &guard_call, remaining, || {
+        with_vault_signer(multisig_account.key(), 0, multisig.treasury_bump, |signer| {
+            execute_batch(data, treasury, allowlist, rate_limited, batch_accounts, signer)
+        })
+    })?;
+
+    for (mint, outflow, inflow) in balances_before.flows(treasury, remaining) {
+        for (direction, amount) in [(LedgerDirection::Outflow, outflow), (LedgerDirection::Inflow, inflow)] {
+            if amount == 0 {
+                continue;
+            }
+            if direction == LedgerDirection::Outflow {
+                apply_outflow(multisig_account.key(), 0, rate_limit_account, &mint, amount)?;
+            }
+            record_flow(
+                multisig_account.key(),
+                ledger_account,
+                0,
+                direction,
+                LedgerSource::Module,
+                &mint,
+                amount,
+                module_program.key(),
+                0,
+            )?;
+        }
+    }
+
+    log!("Module executed");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{multisig_account, TestAccount};
+
+    const CREATOR: Pubkey = [0x21; 32];
+
+    fn update(multisig: &mut Multisig, action: u8, program: Pubkey) -> ProgramResult {
+        let mut payload = [0u8; UpdateModules::LEN];
+        payload[0] = action;
+        payload[1..].copy_from_slice(&program);
+        execute_update_modules(&payload, multisig)
+    }
+
+    #[test]
+    fn modules_are_registered_up_to_the_limit() {
+        let mut account = multisig_account(&CREATOR, &[CREATOR], 1);
+        let multisig = account.state::<Multisig>();
+
+        for module in 1..=Multisig::MAX_MODULES as u8 {
+            assert_eq!(update(multisig, 0, [module; 32]), Ok(()));
+        }
+        assert_eq!(multisig.module_count as usize, Multisig::MAX_MODULES);
+        assert_eq!(update(multisig, 0, [9; 32]), Err(ProgramError::InvalidArgument));
+        assert_eq!(update(multisig, 0, [1; 32]), Err(ProgramError::InvalidArgument));
+    }
+
+    #[test]
+    fn the_program_itself_cannot_be_a_module() {
+        let mut account = multisig_account(&CREATOR, &[CREATOR], 1);
+        let multisig = account.state::<Multisig>();
+        for program in [crate::ID, Pubkey::default()] {
+            assert_eq!(update(multisig, 0, program), Err(ProgramError::InvalidArgument));
+        }
+        assert_eq!(multisig.module_count, 0);
+    }
+
+    #[test]
+    fn unregistering_keeps_the_registry_contiguous() {
+        let mut account = multisig_account(&CREATOR, &[CREATOR], 1);
+        let multisig = account.state::<Multisig>();
+        for module in 1..=3 {
+            update(multisig, 0, [module; 32]).unwrap();
+        }
+
+        assert_eq!(update(multisig, 1, [1; 32]), Ok(()));
+        assert_eq!(multisig.module_count, 2);
+        assert_eq!(multisig.modules[..3], [[3; 32], [2; 32], Pubkey::default()]);
+        assert_eq!(update(multisig, 1, [1; 32]), Err(ProgramError::InvalidArgument));
+        assert_eq!(update(multisig, 2, [2; 32]), Err(ProgramError::InvalidInstructionData));
+    }
+
+    #[test]
+    fn unregistered_programs_cannot_execute() {
+        // Left in the registry past the count, as after an unregistration
+        let mut multisig = multisig_account(&CREATOR, &[CREATOR], 1);
+        multisig.state::<Multisig>().modules[0] = [1; 32];
+
+        let mut authority = TestAccount::new([0x31; 32], [1; 32], 0).signer();
+        let mut module = TestAccount::new([1; 32], [0; 32], 0);
+        let mut others: Vec<TestAccount> =
+            (0..5).map(|index| TestAccount::new([0x40 + index; 32], crate::ID, 0)).collect();
+        let mut accounts = vec![authority.info(), module.info(), multisig.info()];
+        accounts.extend(others.iter_mut().map(|account| account.info()));
+
+        assert_eq!(process_execute_module_instruction(&accounts, &[]), Err(ProgramError::IllegalOwner));
+    }
+}
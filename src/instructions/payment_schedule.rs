@@ -0,0 +1,410 @@
+use pinocchio::sysvars::clock::Clock;
+use pinocchio::sysvars::rent::Rent;
+use pinocchio::sysvars::Sysvar;
+use pinocchio::{
+    account_info::AccountInfo,
+    instruction::{Seed, Signer},
+    program_error::ProgramError,
+    pubkey::{self, Pubkey},
+    ProgramResult,
+};
+use pinocchio_log::log;
+
+use crate::instructions::{
+    apply_outflow, load_guard, record_flow, vault_token_balance, verify_destination, verify_vault, with_guard,
+    with_vault_signer, GuardCall,
+};
+use crate::state::{
+    CancelPaymentSchedule, CreatePaymentSchedule, FixedPayload, LedgerDirection, LedgerSource,
+    PaymentSchedule, Proposal,
+};
+use crate::utils::{create_program_address, transfer_sol, transfer_token};
+
+/// Executes a `CreatePaymentSchedule` proposal on the proposal's vault
+/// Accounts expected (after the common execution accounts)
+/// - payment schedule (`[b"payment_schedule", proposal]`), system program
+pub fn execute_create_payment_schedule(
+    payload: &[u8],
+    payer: &AccountInfo,
+    multisig_account: &AccountInfo,
+    proposal_account: &AccountInfo,
+    proposal: &Proposal,
+    remaining: &[AccountInfo],
+) -> ProgramResult {
+    let create_schedule = CreatePaymentSchedule::from_bytes(payload)?;
+    let amount = create_schedule.amount;
+    let interval = create_schedule.interval;
+    let count = create_schedule.count;
+    let end_time = create_schedule.end_time;
+
+    let [schedule_account, _system_program, ..] = remaining else {
+        log!("Error: Not enough accounts provided to create the payment schedule.");
+        return Err(ProgramError::NotEnoughAccountKeys);
+    };
+
+    if amount == 0 || interval == 0 || (count == 0 && end_time == 0) {
+        log!("Error: A payment schedule needs an amount, an interval, and a payment count or end date.");
+        return Err(ProgramError::InvalidInstructionData);
+    }
+
+    let now = Clock::get()?.unix_timestamp as u64;
+    let first_due = if create_schedule.first_due == 0 { now } else { create_schedule.first_due };
+    if end_time != 0 && end_time < first_due {
+        log!("Error: The schedule ends before its first payment.");
+        return Err(ProgramError::InvalidInstructionData);
+    }
+
+    let (pda_schedule, bump) = pubkey::find_program_address(
+        &[b"payment_schedule", proposal_account.key().as_ref()],
+        &crate::ID,
+    );
+    if &pda_schedule != schedule_account.key() {
+        log!("Error: Invalid payment schedule PDA.");
+        return Err(ProgramError::InvalidArgument);
+    }
+
+    let bump_seed = [bump];
+    let seeds = [
+        Seed::from(b"payment_schedule"),
+        Seed::from(proposal_account.key().as_ref()),
+        Seed::from(&bump_seed),
+    ];
+
+    pinocchio_system::instructions::CreateAccount {
+        from: payer,
+        to: schedule_account,
+        lamports: Rent::get()?.minimum_balance(PaymentSchedule::LEN),
+        space: PaymentSchedule::LEN as u64,
+        owner: &crate::ID,
+    }
+    .invoke_signed(&[Signer::from(&seeds)])?;
+
+    let schedule = PaymentSchedule::from_account_info(schedule_account)?;
+    schedule.proposal = *proposal_account.key();
+    schedule.multisig = *multisig_account.key();
+    schedule.mint = create_schedule.mint;
+    schedule.recipient = create_schedule.recipient;
+    schedule.amount = amount;
+    schedule.interval = interval;
+    schedule.next_due = first_due;
+    schedule.count = count;
+    schedule.end_time = end_time;
+    schedule.payments_made = 0;
+    schedule.proposal_id = proposal.id;
+    schedule.vault_index = proposal.vault_index;
+    schedule.vault_bump = proposal.vault_bump;
+    schedule.bump = bump;
+    schedule.cancelled = 0;
+
+    log!("Created payment schedule of {} every {} seconds", amount, interval);
+    Ok(())
+}
+
+/// Executes a `CancelPaymentSchedule` proposal. Payments already made stay with the
+/// recipient, and no further payment can be cranked
+/// Accounts expected (after the common execution accounts)
+/// - payment schedule
+pub fn execute_cancel_payment_schedule(
+    payload: &[u8],
+    multisig_account: &AccountInfo,
+    proposal: &Proposal,
+    remaining: &[AccountInfo],
+) -> ProgramResult {
+    let schedule_key = CancelPaymentSchedule::from_bytes(payload)?.schedule;
+
+    let [schedule_account, ..] = remaining else {
+        log!("Error: Not enough accounts provided to cancel the payment schedule.");
+        return Err(ProgramError::NotEnoughAccountKeys);
+    };
+
+    if schedule_account.key() != &schedule_key {
+        log!("Error: Payment schedule account does not match the proposal.");
+        return Err(ProgramError::InvalidArgument);
+    }
+    let schedule = load_payment_schedule(schedule_account)?;
+    if schedule.multisig != *multisig_account.key() || schedule.vault_index != proposal.vault_index {
+        log!("Error: Payment schedule does not pay from this vault.");
+        return Err(ProgramError::InvalidArgument);
+    }
+    if schedule.cancelled == 1 || schedule.is_finished() {
+        log!("Error: Payment schedule has already ended.");
+        return Err(ProgramError::InvalidArgument);
+    }
+
+    schedule.cancelled = 1;
+
+    log!("Cancelled payment schedule after {} payments", schedule.payments_made);
+    Ok(())
+}
+
+/// Makes the next due payment of a schedule, signed by the schedule's vault
+/// Anyone can crank a schedule; each call pays a single interval, so missed
+/// payments are caught up by cranking again. While the vault cannot cover a payment
+/// the schedule pauses, and resumes from the unpaid interval once the vault is funded
+/// Accounts expected
+/// - caller (signer), payment schedule, vault, destination (the recipient for SOL,
+///   a token account owned by the recipient for tokens), system or token program,
+///   the vault's rate limit (`[b"rate_limit", multisig, vault_index]`, even if none is set),
+///   the multisig's ledger (`[b"ledger", multisig]`, even if not created), the multisig's
+///   guard (`[b"guard", multisig]`, even if not set)
+/// - vault token account holding the mint (tokens only)
+/// - the guard program, if one is set
+pub fn process_crank_payment_schedule_instruction(accounts: &[AccountInfo], data: &[u8]) -> ProgramResult {
+    crank_payment_schedule(accounts, data, &Clock::get()?)
+}
+
+fn crank_payment_schedule(accounts: &[AccountInfo], data: &[u8], clock: &Clock) -> ProgramResult {
+    let [caller, schedule_account, vault, destination, program, rate_limit_account, ledger_account, guard_account, remaining @ ..] =
+        accounts
+    else {
+        log!("Error: Not enough accounts provided. Expected at least 8.");
+        return Err(ProgramError::NotEnoughAccountKeys);
+    };
+
+    if !caller.is_signer() {
+        log!("Error: The caller must sign the transaction.");
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+
+    // Anyone can crank, so the schedule must be the real PDA and not look-alike bytes
+    let schedule = load_payment_schedule(schedule_account)?;
+
+    verify_vault(&schedule.multisig, schedule.vault_index, schedule.vault_bump, vault)?;
+    verify_destination(destination, program, &schedule.mint, &schedule.recipient)?;
+
+    if schedule.cancelled == 1 {
+        log!("Error: Payment schedule has been cancelled.");
+        return Err(ProgramError::InvalidAccountData);
+    }
+    if schedule.is_finished() {
+        log!("Error: Every payment of this schedule has been made.");
+        return Err(ProgramError::InvalidAccountData);
+    }
+    let now = clock.unix_timestamp as u64;
+    if now < schedule.next_due {
+        log!("Error: The next payment is not due until {}.", schedule.next_due);
+        return Err(ProgramError::InvalidArgument);
+    }
+
+    // Checked up front so a short vault leaves the schedule untouched rather than
+    // failing in the transfer
+    let available = if schedule.mint == Pubkey::default() {
+        vault.lamports()
+    } else {
+        remaining
+            .first()
+            .and_then(|source| vault_token_balance(vault, source))
+            .filter(|(mint, _)| mint == &schedule.mint)
+            .map_or(0, |(_, amount)| amount)
+    };
+    if available < schedule.amount {
+        log!("Error: The vault cannot cover the payment due {}.", schedule.next_due);
+        return Err(ProgramError::InsufficientFunds);
+    }
+
+    // Advanced before the transfer so the same interval cannot be paid twice
+    schedule.next_due += schedule.interval;
+    schedule.payments_made += 1;
+
+    apply_outflow(&schedule.multisig, schedule.vault_index, rate_limit_account, &schedule.mint, schedule.amount)?;
+    record_flow(
+        &schedule.multisig,
+        ledger_account,
+        schedule.vault_index,
+        LedgerDirection::Outflow,
+        LedgerSource::PaymentSchedule,
+        &schedule.mint,
+        schedule.amount,
+        &schedule.recipient,
+        schedule.proposal_id,
+    )?;
+
+    let guard = load_guard(&schedule.multisig, guard_account)?;
+    let guard_call = GuardCall {
+        source: LedgerSource::PaymentSchedule,
+        multisig: &schedule.multisig,
+        vault,
+        vault_index: schedule.vault_index,
+        authority: schedule_account,
+        proposal_id: schedule.proposal_id,
+        kind: 0,
+        data,
+    };
+
+    with_guard(guard, &guard_call, remaining, || {
+        with_vault_signer(&schedule.multisig, schedule.vault_index, schedule.vault_bump, |signer| {
+            if schedule.mint == Pubkey::default() {
+                transfer_sol(vault, destination, schedule.amount, signer)
+            } else {
+                let [source, ..] = remaining else {
+                    log!("Error: Vault token account is required for token payments.");
+                    return Err(ProgramError::NotEnoughAccountKeys);
+                };
+                transfer_token(source, destination, vault, &schedule.mint, program, schedule.amount, signer)
+            }
+        })
+    })?;
+
+    log!("Made scheduled payment {} of {}", schedule.payments_made, schedule.amount);
+    Ok(())
+}
+
+/// Loads a payment schedule, checking it is the `[b"payment_schedule", proposal]` PDA it
+/// claims to be
+pub fn load_payment_schedule(schedule_account: &AccountInfo) -> Result<&mut PaymentSchedule, ProgramError> {
+    if schedule_account.owner() != &crate::ID {
+        log!("Error: Payment schedule must be owned by this program.");
+        return Err(ProgramError::IllegalOwner);
+    }
+    let schedule = PaymentSchedule::from_account_info(schedule_account)?;
+
+    let pda_schedule = create_program_address(
+        &[b"payment_schedule", schedule.proposal.as_ref(), &[schedule.bump]],
+        &crate::ID,
+    )?;
+    if &pda_schedule != schedule_account.key() {
+        log!("Error: Invalid payment schedule PDA.");
+        return Err(ProgramError::InvalidArgument);
+    }
+    Ok(schedule)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::ProposalStatus;
+    use crate::test_utils::{program_address, proposal_account, TestAccount};
+
+    const MULTISIG: Pubkey = [0x21; 32];
+    const PROPOSAL: Pubkey = [0x22; 32];
+    const RECIPIENT: Pubkey = [0x23; 32];
+    const SYSTEM: Pubkey = pinocchio_system::ID;
+
+    /// A SOL schedule paying 100 every 10 seconds from 1_000, from the treasury
+    fn schedule_account(count: u64, end_time: u64) -> TestAccount {
+        let key = program_address(&[b"payment_schedule", &PROPOSAL, &[255]]);
+        let mut account = TestAccount::new(key, crate::ID, PaymentSchedule::LEN);
+        let schedule = account.state::<PaymentSchedule>();
+        schedule.proposal = PROPOSAL;
+        schedule.multisig = MULTISIG;
+        schedule.recipient = RECIPIENT;
+        schedule.amount = 100;
+        schedule.interval = 10;
+        schedule.next_due = 1_000;
+        schedule.count = count;
+        schedule.end_time = end_time;
+        schedule.vault_bump = 255;
+        schedule.bump = 255;
+        account
+    }
+
+    /// Cranks `schedule` at `now` with a treasury holding `vault_lamports`, and no rate
+    /// limit, ledger or guard
+    fn crank(schedule: &mut TestAccount, vault_lamports: u64, now: i64) -> ProgramResult {
+        let mut caller = TestAccount::new([0x24; 32], SYSTEM, 0).signer();
+        let vault_key = program_address(&[b"treasury", &MULTISIG, &[255]]);
+        let mut vault = TestAccount::new(vault_key, SYSTEM, 0).with_lamports(vault_lamports);
+        let mut destination = TestAccount::new(RECIPIENT, SYSTEM, 0);
+        let mut program = TestAccount::new(SYSTEM, [0x25; 32], 0);
+        let rate_limit_key = program_address(&[b"rate_limit", &MULTISIG, &[0], &[255]]);
+        let mut rate_limit = TestAccount::new(rate_limit_key, SYSTEM, 0);
+        let mut ledger = TestAccount::new(program_address(&[b"ledger", &MULTISIG, &[255]]), SYSTEM, 0);
+        let mut guard = TestAccount::new(program_address(&[b"guard", &MULTISIG, &[255]]), SYSTEM, 0);
+        let accounts = [
+            caller.info(),
+            schedule.info(),
+            vault.info(),
+            destination.info(),
+            program.info(),
+            rate_limit.info(),
+            ledger.info(),
+            guard.info(),
+        ];
+
+        let clock = Clock { unix_timestamp: now, ..Clock::default() };
+        crank_payment_schedule(&accounts, &[], &clock)
+    }
+
+    #[test]
+    fn payments_can_only_be_cranked_once_due() {
+        let mut schedule = schedule_account(0, 10_000);
+
+        assert_eq!(crank(&mut schedule, 1_000, 999), Err(ProgramError::InvalidArgument));
+        assert_eq!(crank(&mut schedule, 1_000, 1_000), Ok(()));
+        // The same interval cannot be paid twice
+        assert_eq!(crank(&mut schedule, 1_000, 1_005), Err(ProgramError::InvalidArgument));
+        assert_eq!(crank(&mut schedule, 1_000, 1_010), Ok(()));
+
+        let state = schedule.state::<PaymentSchedule>();
+        assert_eq!((state.payments_made, state.next_due), (2, 1_020));
+    }
+
+    #[test]
+    fn missed_payments_are_caught_up_one_interval_per_crank() {
+        let mut schedule = schedule_account(0, 10_000);
+
+        // Three intervals (1_000, 1_010, 1_020) are due by 1_025
+        for _ in 0..3 {
+            assert_eq!(crank(&mut schedule, 1_000, 1_025), Ok(()));
+        }
+        assert_eq!(crank(&mut schedule, 1_000, 1_025), Err(ProgramError::InvalidArgument));
+
+        let state = schedule.state::<PaymentSchedule>();
+        assert_eq!((state.payments_made, state.next_due), (3, 1_030));
+    }
+
+    #[test]
+    fn an_underfunded_vault_pauses_the_schedule() {
+        let mut schedule = schedule_account(0, 10_000);
+
+        assert_eq!(crank(&mut schedule, 99, 1_000), Err(ProgramError::InsufficientFunds));
+        let state = schedule.state::<PaymentSchedule>();
+        assert_eq!((state.payments_made, state.next_due), (0, 1_000));
+
+        // Once funded the unpaid interval is paid, then the ones that fell due meanwhile
+        assert_eq!(crank(&mut schedule, 1_000, 1_015), Ok(()));
+        assert_eq!(crank(&mut schedule, 1_000, 1_015), Ok(()));
+        let state = schedule.state::<PaymentSchedule>();
+        assert_eq!((state.payments_made, state.next_due), (2, 1_020));
+    }
+
+    #[test]
+    fn finished_schedules_cannot_be_cranked() {
+        let mut schedule = schedule_account(2, 0);
+        assert_eq!(crank(&mut schedule, 1_000, 2_000), Ok(()));
+        assert_eq!(crank(&mut schedule, 1_000, 2_000), Ok(()));
+        assert_eq!(crank(&mut schedule, 1_000, 2_000), Err(ProgramError::InvalidAccountData));
+    }
+
+    #[test]
+    fn cancelled_schedules_cannot_be_cranked() {
+        let mut schedule = schedule_account(0, 10_000);
+        let mut proposal = proposal_account(&MULTISIG, 1, ProposalStatus::Succeeded);
+        let mut multisig = TestAccount::new(MULTISIG, crate::ID, 0);
+        let payload = program_address(&[b"payment_schedule", &PROPOSAL, &[255]]);
+
+        let (multisig, remaining) = (multisig.info(), [schedule.info()]);
+        let proposal = proposal.state::<Proposal>();
+        assert_eq!(execute_cancel_payment_schedule(&payload, &multisig, proposal, &remaining), Ok(()));
+        assert_eq!(
+            execute_cancel_payment_schedule(&payload, &multisig, proposal, &remaining),
+            Err(ProgramError::InvalidArgument)
+        );
+
+        assert_eq!(crank(&mut schedule, 1_000, 1_000), Err(ProgramError::InvalidAccountData));
+    }
+
+    #[test]
+    fn only_schedules_of_the_proposal_vault_can_be_cancelled() {
+        let mut schedule = schedule_account(0, 10_000);
+        let mut proposal = proposal_account(&MULTISIG, 1, ProposalStatus::Succeeded);
+        proposal.state::<Proposal>().vault_index = 1;
+        let mut multisig = TestAccount::new(MULTISIG, crate::ID, 0);
+        let payload = program_address(&[b"payment_schedule", &PROPOSAL, &[255]]);
+
+        let (multisig, remaining) = (multisig.info(), [schedule.info()]);
+        let result = execute_cancel_payment_schedule(&payload, &multisig, proposal.state::<Proposal>(), &remaining);
+        assert_eq!(result, Err(ProgramError::InvalidArgument));
+        assert_eq!(schedule.state::<PaymentSchedule>().cancelled, 0);
+    }
+}
@@ -0,0 +1,150 @@
+use pinocchio::sysvars::rent::Rent;
+use pinocchio::sysvars::Sysvar;
+use pinocchio::{
+    account_info::AccountInfo,
+    instruction::{Seed, Signer},
+    program_error::ProgramError,
+    pubkey::{self, Pubkey},
+    ProgramResult,
+};
+use pinocchio_log::log;
+
+use crate::instructions::STAKE_PROGRAM_ID;
+use crate::state::{FixedPayload, Multisig, ProgramAllowlist, UpdateProgramAllowlist};
+use crate::utils::{TOKEN_2022_PROGRAM_ID, TOKEN_PROGRAM_ID};
+
+/// Programs allowed until the multisig changes its allowlist
+pub const DEFAULT_ALLOWED_PROGRAMS: [Pubkey; 4] =
+    [pinocchio_system::ID, TOKEN_PROGRAM_ID, TOKEN_2022_PROGRAM_ID, STAKE_PROGRAM_ID];
+
+/// Approvals an `UpdateProgramAllowlist` proposal needs at least: two thirds of the members
+pub fn allowlist_threshold(multisig: &Multisig) -> u64 {
+    (multisig.member_count as u64 * 2).div_ceil(3)
+}
+
+/// Checks that `allowlist_account` is the multisig's allowlist PDA and loads it
+/// Returns `None` if the multisig still uses the default programs
+pub fn load_program_allowlist<'a>(
+    multisig_key: &Pubkey,
+    allowlist_account: &'a AccountInfo,
+) -> Result<Option<&'a mut ProgramAllowlist>, ProgramError> {
+    let (pda_allowlist, _) = pubkey::find_program_address(
+        &[b"program_allowlist", multisig_key.as_ref()],
+        &crate::ID,
+    );
+    if &pda_allowlist != allowlist_account.key() {
+        log!("Error: Invalid program allowlist PDA.");
+        return Err(ProgramError::InvalidArgument);
+    }
+
+    if allowlist_account.owner() != &crate::ID {
+        return Ok(None);
+    }
+    Ok(Some(ProgramAllowlist::from_account_info(allowlist_account)?))
+}
+
+/// Whether the multisig's vaults may invoke `program`
+pub fn is_program_allowed(allowlist: Option<&ProgramAllowlist>, program: &Pubkey) -> bool {
+    match allowlist {
+        Some(allowlist) => allowlist.position(program).is_some(),
+        None => DEFAULT_ALLOWED_PROGRAMS.contains(program),
+    }
+}
+
+/// Fails unless the multisig's allowlist lets its vaults invoke `program`
+pub fn require_program_allowed(allowlist: Option<&ProgramAllowlist>, program: &Pubkey) -> ProgramResult {
+    if !is_program_allowed(allowlist, program) {
+        log!("Error: Program is not on the multisig's allowlist.");
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    Ok(())
+}
+
+/// Executes an `UpdateProgramAllowlist` proposal, creating the allowlist (starting from
+/// the default programs) on first use
+/// Uses the allowlist account passed to every execution
+/// Accounts expected (after the common execution accounts)
+/// - system program
+pub fn execute_update_program_allowlist(
+    payload: &[u8],
+    payer: &AccountInfo,
+    multisig_account: &AccountInfo,
+    allowlist_account: &AccountInfo,
+    remaining: &[AccountInfo],
+) -> ProgramResult {
+    let update = UpdateProgramAllowlist::from_bytes(payload)?;
+    let program = update.program;
+
+    let [_system_program, ..] = remaining else {
+        log!("Error: Not enough accounts provided to update the program allowlist.");
+        return Err(ProgramError::NotEnoughAccountKeys);
+    };
+
+    let allowlist = match load_program_allowlist(multisig_account.key(), allowlist_account)? {
+        Some(allowlist) => allowlist,
+        None => {
+            log!("Creating program allowlist");
+            let (_, bump) = pubkey::find_program_address(
+                &[b"program_allowlist", multisig_account.key().as_ref()],
+                &crate::ID,
+            );
+            let bump_seed = [bump];
+            let seeds = [
+                Seed::from(b"program_allowlist"),
+                Seed::from(multisig_account.key().as_ref()),
+                Seed::from(&bump_seed),
+            ];
+
+            pinocchio_system::instructions::CreateAccount {
+                from: payer,
+                to: allowlist_account,
+                lamports: Rent::get()?.minimum_balance(ProgramAllowlist::LEN),
+                space: ProgramAllowlist::LEN as u64,
+                owner: &crate::ID,
+            }
+            .invoke_signed(&[Signer::from(&seeds)])?;
+
+            let allowlist = ProgramAllowlist::from_account_info(allowlist_account)?;
+            allowlist.multisig = *multisig_account.key();
+            allowlist.programs = [Pubkey::default(); ProgramAllowlist::MAX_PROGRAMS];
+            allowlist.programs[..DEFAULT_ALLOWED_PROGRAMS.len()].copy_from_slice(&DEFAULT_ALLOWED_PROGRAMS);
+            allowlist.program_count = DEFAULT_ALLOWED_PROGRAMS.len() as u8;
+            allowlist.bump = bump;
+            allowlist
+        }
+    };
+
+    match (update.action, allowlist.position(&program)) {
+        (0, Some(_)) => {
+            log!("Error: Program is already allowed.");
+            return Err(ProgramError::InvalidArgument);
+        }
+        (0, None) => {
+            if allowlist.program_count as usize == ProgramAllowlist::MAX_PROGRAMS {
+                log!("Error: The program allowlist is full.");
+                return Err(ProgramError::AccountDataTooSmall);
+            }
+            allowlist.programs[allowlist.program_count as usize] = program;
+            allowlist.program_count += 1;
+            log!("Allowed program");
+        }
+        (1, Some(index)) => {
+            // Swap in the last entry to keep the list contiguous
+            let last = allowlist.program_count as usize - 1;
+            allowlist.programs[index] = allowlist.programs[last];
+            allowlist.programs[last] = Pubkey::default();
+            allowlist.program_count -= 1;
+            log!("Disallowed program");
+        }
+        (1, None) => {
+            log!("Error: Program is not on the allowlist.");
+            return Err(ProgramError::InvalidArgument);
+        }
+        _ => {
+            log!("Error: Invalid program allowlist action.");
+            return Err(ProgramError::InvalidInstructionData);
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,215 @@
+use pinocchio::{
+    account_info::AccountInfo,
+    cpi::invoke_signed,
+    instruction::{AccountMeta, Instruction, Signer},
+    program_error::ProgramError,
+    pubkey::{self, Pubkey},
+    ProgramResult,
+};
+use pinocchio_log::log;
+
+use crate::instructions::require_program_allowed;
+use crate::state::{FixedPayload, ProgramAllowlist, ProgramUpgrade, SetUpgradeAuthority};
+
+pub const BPF_LOADER_UPGRADEABLE_ID: Pubkey =
+    pinocchio_pubkey::pubkey!("BPFLoaderUpgradeab1e11111111111111111111111");
+
+// Upgradeable loader instruction tags (bincode encoded u32)
+const UPGRADE: [u8; 4] = [3, 0, 0, 0];
+const SET_AUTHORITY: [u8; 4] = [4, 0, 0, 0];
+
+// Upgradeable loader account state tag of buffers, and the size of their metadata
+const BUFFER_TAG: [u8; 4] = [1, 0, 0, 0];
+const BUFFER_HEADER_LEN: usize = 4 + 1 + 32;
+
+/// Checks that `program_data` is the program data account of `program`
+fn verify_program_data(program: &Pubkey, program_data: &Pubkey) -> ProgramResult {
+    let (expected, _) = pubkey::find_program_address(&[program.as_ref()], &BPF_LOADER_UPGRADEABLE_ID);
+    if &expected != program_data {
+        log!("Error: Program data account does not belong to the program.");
+        return Err(ProgramError::InvalidArgument);
+    }
+    Ok(())
+}
+
+/// Checks that `buffer` is an upgradeable loader buffer whose authority is `vault`
+/// Only the vault can then write to the buffer, so the code members vote on when the
+/// proposal is created is the code the upgrade deploys
+pub fn verify_upgrade_buffer(buffer: &AccountInfo, expected: &Pubkey, vault: &Pubkey) -> ProgramResult {
+    if buffer.key() != expected {
+        log!("Error: Buffer account does not match the one recorded in the proposal.");
+        return Err(ProgramError::InvalidArgument);
+    }
+    if buffer.owner() != &BPF_LOADER_UPGRADEABLE_ID {
+        log!("Error: Buffer is not owned by the upgradeable loader.");
+        return Err(ProgramError::IllegalOwner);
+    }
+
+    // `UpgradeableLoaderState::Buffer`: tag 1 (u32), then the authority as an `Option<Pubkey>`
+    let data = buffer.try_borrow_data()?;
+    if data.len() < BUFFER_HEADER_LEN
+        || data[..4] != BUFFER_TAG
+        || data[4] != 1
+        || &data[5..BUFFER_HEADER_LEN] != vault.as_ref()
+    {
+        log!("Error: The buffer's authority must be the vault.");
+        return Err(ProgramError::InvalidAccountData);
+    }
+    Ok(())
+}
+
+/// Executes a `ProgramUpgrade` proposal through the upgradeable loader
+/// Accounts expected (after the common execution accounts)
+/// - program data, program, buffer, spill, rent sysvar, clock sysvar
+pub fn execute_program_upgrade(
+    payload: &[u8],
+    treasury: &AccountInfo,
+    allowlist: Option<&ProgramAllowlist>,
+    remaining: &[AccountInfo],
+    signer: &Signer,
+) -> ProgramResult {
+    let upgrade = ProgramUpgrade::from_bytes(payload)?;
+    require_program_allowed(allowlist, &BPF_LOADER_UPGRADEABLE_ID)?;
+
+    let [program_data, program, buffer, spill, rent, clock, ..] = remaining else {
+        log!("Error: Not enough accounts provided for the upgrade.");
+        return Err(ProgramError::NotEnoughAccountKeys);
+    };
+
+    if program.key() != &upgrade.program
+        || program_data.key() != &upgrade.program_data
+        || buffer.key() != &upgrade.buffer
+        || spill.key() != &upgrade.spill
+    {
+        log!("Error: Accounts do not match the ones recorded in the proposal.");
+        return Err(ProgramError::InvalidArgument);
+    }
+    verify_program_data(&upgrade.program, &upgrade.program_data)?;
+    verify_upgrade_buffer(buffer, &upgrade.buffer, treasury.key())?;
+
+    log!("Upgrading program via the upgradeable loader");
+    invoke_signed(
+        &Instruction {
+            program_id: &BPF_LOADER_UPGRADEABLE_ID,
+            accounts: &[
+                AccountMeta::writable(program_data.key()),
+                AccountMeta::writable(program.key()),
+                AccountMeta::writable(buffer.key()),
+                AccountMeta::writable(spill.key()),
+                AccountMeta::readonly(rent.key()),
+                AccountMeta::readonly(clock.key()),
+                AccountMeta::readonly_signer(treasury.key()),
+            ],
+            data: &UPGRADE,
+        },
+        &[program_data, program, buffer, spill, rent, clock, treasury],
+        core::slice::from_ref(signer),
+    )
+}
+
+/// Executes a `SetUpgradeAuthority` proposal through the upgradeable loader
+/// Accounts expected (after the common execution accounts)
+/// - program data, new authority (omitted when revoking the authority)
+pub fn execute_set_upgrade_authority(
+    payload: &[u8],
+    treasury: &AccountInfo,
+    allowlist: Option<&ProgramAllowlist>,
+    remaining: &[AccountInfo],
+    signer: &Signer,
+) -> ProgramResult {
+    let set_authority = SetUpgradeAuthority::from_bytes(payload)?;
+    require_program_allowed(allowlist, &BPF_LOADER_UPGRADEABLE_ID)?;
+
+    let [program_data, remaining @ ..] = remaining else {
+        log!("Error: Not enough accounts provided for the authority change.");
+        return Err(ProgramError::NotEnoughAccountKeys);
+    };
+
+    if program_data.key() != &set_authority.program_data {
+        log!("Error: Program data account does not match the one recorded in the proposal.");
+        return Err(ProgramError::InvalidArgument);
+    }
+    verify_program_data(&set_authority.program, &set_authority.program_data)?;
+
+    if set_authority.new_authority == Pubkey::default() {
+        log!("Revoking upgrade authority, the program becomes immutable");
+        return invoke_signed(
+            &Instruction {
+                program_id: &BPF_LOADER_UPGRADEABLE_ID,
+                accounts: &[
+                    AccountMeta::writable(program_data.key()),
+                    AccountMeta::readonly_signer(treasury.key()),
+                ],
+                data: &SET_AUTHORITY,
+            },
+            &[program_data, treasury],
+            core::slice::from_ref(signer),
+        );
+    }
+
+    let [new_authority, ..] = remaining else {
+        log!("Error: New authority account is required.");
+        return Err(ProgramError::NotEnoughAccountKeys);
+    };
+    if new_authority.key() != &set_authority.new_authority {
+        log!("Error: New authority does not match the one recorded in the proposal.");
+        return Err(ProgramError::InvalidArgument);
+    }
+
+    log!("Transferring upgrade authority");
+    invoke_signed(
+        &Instruction {
+            program_id: &BPF_LOADER_UPGRADEABLE_ID,
+            accounts: &[
+                AccountMeta::writable(program_data.key()),
+                AccountMeta::readonly_signer(treasury.key()),
+                AccountMeta::readonly(new_authority.key()),
+            ],
+            data: &SET_AUTHORITY,
+        },
+        &[program_data, treasury, new_authority],
+        core::slice::from_ref(signer),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::TestAccount;
+
+    const BUFFER: Pubkey = [3; 32];
+    const VAULT: Pubkey = [4; 32];
+
+    fn buffer_account(owner: Pubkey, authority: Option<Pubkey>) -> TestAccount {
+        let mut buffer = TestAccount::new(BUFFER, owner, BUFFER_HEADER_LEN + 16);
+        let data = buffer.state::<[u8; BUFFER_HEADER_LEN]>();
+        data[..4].copy_from_slice(&BUFFER_TAG);
+        if let Some(authority) = authority {
+            data[4] = 1;
+            data[5..].copy_from_slice(&authority);
+        }
+        buffer
+    }
+
+    #[test]
+    fn buffers_of_the_vault_are_accepted() {
+        let mut buffer = buffer_account(BPF_LOADER_UPGRADEABLE_ID, Some(VAULT));
+        assert_eq!(verify_upgrade_buffer(&buffer.info(), &BUFFER, &VAULT), Ok(()));
+        assert_eq!(
+            verify_upgrade_buffer(&buffer.info(), &[5; 32], &VAULT),
+            Err(ProgramError::InvalidArgument)
+        );
+    }
+
+    #[test]
+    fn buffers_others_can_write_to_are_rejected() {
+        for (owner, authority, error) in [
+            (BPF_LOADER_UPGRADEABLE_ID, Some([6; 32]), ProgramError::InvalidAccountData),
+            (BPF_LOADER_UPGRADEABLE_ID, None, ProgramError::InvalidAccountData),
+            (crate::ID, Some(VAULT), ProgramError::IllegalOwner),
+        ] {
+            let mut buffer = buffer_account(owner, authority);
+            assert_eq!(verify_upgrade_buffer(&buffer.info(), &BUFFER, &VAULT), Err(error));
+        }
+    }
+}
@@ -0,0 +1,223 @@
+use pinocchio::sysvars::clock::Clock;
+use pinocchio::sysvars::rent::Rent;
+use pinocchio::sysvars::Sysvar;
+use pinocchio::{
+    account_info::AccountInfo,
+    instruction::{Seed, Signer},
+    program_error::ProgramError,
+    pubkey::{self, Pubkey},
+    ProgramResult,
+};
+use pinocchio_log::log;
+
+use crate::state::{AssetRateLimit, FixedPayload, RateLimit, SetRateLimit};
+use crate::utils::{find_program_address, TOKEN_2022_PROGRAM_ID, TOKEN_PROGRAM_ID};
+
+/// Checks that `rate_limit_account` is the rate limit PDA of the vault and loads it
+/// Returns `None` if no rate limit has been set on the vault
+pub fn load_rate_limit<'a>(
+    multisig_key: &Pubkey,
+    vault_index: u8,
+    rate_limit_account: &'a AccountInfo,
+) -> Result<Option<&'a mut RateLimit>, ProgramError> {
+    let (pda_rate_limit, _) = find_program_address(
+        &[b"rate_limit", multisig_key.as_ref(), &[vault_index]],
+        &crate::ID,
+    );
+    if &pda_rate_limit != rate_limit_account.key() {
+        log!("Error: Invalid rate limit PDA.");
+        return Err(ProgramError::InvalidArgument);
+    }
+
+    if rate_limit_account.owner() != &crate::ID {
+        return Ok(None);
+    }
+    Ok(Some(RateLimit::from_account_info(rate_limit_account)?))
+}
+
+/// Counts `amount` of `mint` leaving a vault against the vault's rate limit
+pub fn apply_outflow(
+    multisig_key: &Pubkey,
+    vault_index: u8,
+    rate_limit_account: &AccountInfo,
+    mint: &Pubkey,
+    amount: u64,
+) -> ProgramResult {
+    let Some(rate_limit) = load_rate_limit(multisig_key, vault_index, rate_limit_account)? else {
+        return Ok(());
+    };
+
+    let now = Clock::get()?.unix_timestamp as u64;
+    if rate_limit.record_outflow(mint, amount, now).is_err() {
+        log!("Error: Outflow of {} exceeds the vault's rate limit.", amount);
+        return Err(ProgramError::InsufficientFunds);
+    }
+    Ok(())
+}
+
+/// SOL and token balances a vault holds in a set of accounts
+/// Taken before and after an execution to measure what left the vault
+pub struct VaultBalances {
+    pub lamports: u64,
+    pub tokens: Vec<(Pubkey, Pubkey, u64)>, // (token account, mint, amount)
+}
+
+impl VaultBalances {
+    /// Reads the vault's lamports and every token account in `accounts` it owns
+    pub fn snapshot(vault: &AccountInfo, accounts: &[AccountInfo]) -> Self {
+        let mut tokens = Vec::new();
+        for account in accounts {
+            if let Some((mint, amount)) = vault_token_balance(vault, account) {
+                if !tokens.iter().any(|(key, _, _)| key == account.key()) {
+                    tokens.push((*account.key(), mint, amount));
+                }
+            }
+        }
+
+        Self {
+            lamports: vault.lamports(),
+            tokens,
+        }
+    }
+
+    /// Returns what left and what entered the vault per asset (the default pubkey for SOL)
+    /// since `self` was taken, as `(asset, outflow, inflow)`
+    pub fn flows(&self, vault: &AccountInfo, accounts: &[AccountInfo]) -> Vec<(Pubkey, u64, u64)> {
+        let mut flows: Vec<(Pubkey, u64, u64)> = Vec::new();
+        let mut add = |mint: Pubkey, before: u64, after: u64| {
+            if before == after {
+                return;
+            }
+            let (outflow, inflow) = (before.saturating_sub(after), after.saturating_sub(before));
+            match flows.iter_mut().find(|(asset, _, _)| *asset == mint) {
+                Some((_, total_out, total_in)) => {
+                    *total_out += outflow;
+                    *total_in += inflow;
+                }
+                None => flows.push((mint, outflow, inflow)),
+            }
+        };
+
+        add(Pubkey::default(), self.lamports, vault.lamports());
+        for (key, mint, before) in &self.tokens {
+            // A token account that was closed or reassigned counts as fully spent
+            let after = accounts
+                .iter()
+                .find(|account| account.key() == key)
+                .and_then(|account| vault_token_balance(vault, account))
+                .map_or(0, |(_, amount)| amount);
+            add(*mint, *before, after);
+        }
+
+        flows
+    }
+}
+
+/// Returns the mint and amount of `account` if it is a token account owned by the vault
+pub fn vault_token_balance(vault: &AccountInfo, account: &AccountInfo) -> Option<(Pubkey, u64)> {
+    if account.owner() != &TOKEN_PROGRAM_ID && account.owner() != &TOKEN_2022_PROGRAM_ID {
+        return None;
+    }
+    let data = account.try_borrow_data().ok()?;
+    if data.len() < 165 || &data[32..64] != vault.key().as_ref() {
+        return None;
+    }
+    Some((
+        data[0..32].try_into().unwrap(),
+        u64::from_le_bytes(data[64..72].try_into().unwrap()),
+    ))
+}
+
+/// Executes a `SetRateLimit` proposal on the proposal's vault
+/// Uses the rate limit account passed to every execution, creating it on first use
+/// Accounts expected (after the common execution accounts)
+/// - system program
+pub fn execute_set_rate_limit(
+    payload: &[u8],
+    payer: &AccountInfo,
+    multisig_key: &Pubkey,
+    vault_index: u8,
+    rate_limit_account: &AccountInfo,
+) -> ProgramResult {
+    let set_limit = SetRateLimit::from_bytes(payload)?;
+    let mint = set_limit.mint;
+    let max_outflow = set_limit.max_outflow;
+    let window = set_limit.window;
+
+    if max_outflow != 0 && window == 0 {
+        log!("Error: A rate limit needs a window.");
+        return Err(ProgramError::InvalidInstructionData);
+    }
+
+    let rate_limit = match load_rate_limit(multisig_key, vault_index, rate_limit_account)? {
+        Some(rate_limit) => rate_limit,
+        None => {
+            log!("Creating rate limit for vault {}", vault_index);
+            let index_seed = [vault_index];
+            let (_, bump) = pubkey::find_program_address(
+                &[b"rate_limit", multisig_key.as_ref(), &index_seed],
+                &crate::ID,
+            );
+            let bump_seed = [bump];
+            let seeds = [
+                Seed::from(b"rate_limit"),
+                Seed::from(multisig_key.as_ref()),
+                Seed::from(&index_seed),
+                Seed::from(&bump_seed),
+            ];
+
+            pinocchio_system::instructions::CreateAccount {
+                from: payer,
+                to: rate_limit_account,
+                lamports: Rent::get()?.minimum_balance(RateLimit::LEN),
+                space: RateLimit::LEN as u64,
+                owner: &crate::ID,
+            }
+            .invoke_signed(&[Signer::from(&seeds)])?;
+
+            let rate_limit = RateLimit::from_account_info(rate_limit_account)?;
+            rate_limit.multisig = *multisig_key;
+            rate_limit.vault_index = vault_index;
+            rate_limit.bump = bump;
+            rate_limit
+        }
+    };
+
+    let now = Clock::get()?.unix_timestamp as u64;
+    match (rate_limit.position(&mint), max_outflow) {
+        (Some(index), 0) => {
+            // Swap in the last entry to keep the list contiguous
+            let last = rate_limit.limit_count as usize - 1;
+            rate_limit.limits[index] = rate_limit.limits[last];
+            rate_limit.limit_count -= 1;
+            log!("Removed rate limit");
+        }
+        (Some(index), _) => {
+            let limit = &mut rate_limit.limits[index];
+            limit.max_outflow = max_outflow;
+            limit.set_window(window, now);
+            log!("Updated rate limit to {} per {} seconds", max_outflow, window);
+        }
+        (None, 0) => {
+            log!("Error: No rate limit to remove for this asset.");
+            return Err(ProgramError::InvalidArgument);
+        }
+        (None, _) => {
+            if rate_limit.limit_count as usize == RateLimit::MAX_LIMITS {
+                log!("Error: The vault already has the maximum number of rate limits.");
+                return Err(ProgramError::AccountDataTooSmall);
+            }
+            rate_limit.limits[rate_limit.limit_count as usize] = AssetRateLimit {
+                mint,
+                max_outflow,
+                window,
+                buckets: [0; AssetRateLimit::BUCKETS],
+                last_bucket: 0,
+            };
+            rate_limit.limit_count += 1;
+            log!("Added rate limit of {} per {} seconds", max_outflow, window);
+        }
+    }
+
+    Ok(())
+}
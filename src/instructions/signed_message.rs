@@ -0,0 +1,72 @@
+use pinocchio::sysvars::clock::Clock;
+use pinocchio::sysvars::rent::Rent;
+use pinocchio::sysvars::Sysvar;
+use pinocchio::{
+    account_info::AccountInfo,
+    instruction::{Seed, Signer},
+    program_error::ProgramError,
+    pubkey,
+    ProgramResult,
+};
+use pinocchio_log::log;
+
+use crate::state::{ApproveMessage, FixedPayload, Proposal, SignedMessage};
+
+/// Executes an `ApproveMessage` proposal, recording the message hash
+/// Accounts expected (after the common execution accounts)
+/// - signed message (`[b"signed_message", multisig, hash]`), system program
+pub fn execute_approve_message(
+    payload: &[u8],
+    payer: &AccountInfo,
+    multisig_account: &AccountInfo,
+    proposal: &Proposal,
+    remaining: &[AccountInfo],
+) -> ProgramResult {
+    let hash = ApproveMessage::from_bytes(payload)?.hash;
+
+    let [message_account, _system_program, ..] = remaining else {
+        log!("Error: Not enough accounts provided to record the message.");
+        return Err(ProgramError::NotEnoughAccountKeys);
+    };
+
+    if message_account.owner() == &crate::ID {
+        log!("Error: This message has already been approved.");
+        return Err(ProgramError::AccountAlreadyInitialized);
+    }
+
+    let (pda_message, bump) = pubkey::find_program_address(
+        &[b"signed_message", multisig_account.key().as_ref(), &hash],
+        &crate::ID,
+    );
+    if &pda_message != message_account.key() {
+        log!("Error: Invalid signed message PDA.");
+        return Err(ProgramError::InvalidArgument);
+    }
+
+    let bump_seed = [bump];
+    let seeds = [
+        Seed::from(b"signed_message"),
+        Seed::from(multisig_account.key().as_ref()),
+        Seed::from(&hash),
+        Seed::from(&bump_seed),
+    ];
+
+    pinocchio_system::instructions::CreateAccount {
+        from: payer,
+        to: message_account,
+        lamports: Rent::get()?.minimum_balance(SignedMessage::LEN),
+        space: SignedMessage::LEN as u64,
+        owner: &crate::ID,
+    }
+    .invoke_signed(&[Signer::from(&seeds)])?;
+
+    let message = SignedMessage::from_account_info(message_account)?;
+    message.multisig = *multisig_account.key();
+    message.hash = hash;
+    message.proposal_id = proposal.id;
+    message.approved_at = Clock::get()?.unix_timestamp as u64;
+    message.bump = bump;
+
+    log!("Recorded approved message");
+    Ok(())
+}
@@ -0,0 +1,203 @@
+use pinocchio::sysvars::clock::Clock;
+use pinocchio::sysvars::rent::Rent;
+use pinocchio::sysvars::Sysvar;
+use pinocchio::{
+    account_info::AccountInfo,
+    instruction::{Seed, Signer},
+    program_error::ProgramError,
+    pubkey::{self, Pubkey},
+    ProgramResult,
+};
+use pinocchio_log::log;
+
+use crate::instructions::{
+    apply_outflow, load_guard, record_flow, verify_vault, with_guard, with_vault_signer, GuardCall,
+};
+use crate::state::{
+    CreateSpendingLimit, FixedPayload, LedgerDirection, LedgerSource, Multisig, Proposal, SpendingLimit,
+    SpendingPeriod,
+};
+use crate::utils::{transfer_sol, transfer_token};
+
+/// Executes a `CreateSpendingLimit` proposal on the proposal's vault
+/// Accounts expected (after the common execution accounts)
+/// - spending limit (`[b"spending_limit", proposal]`), system program
+pub fn execute_create_spending_limit(
+    payload: &[u8],
+    payer: &AccountInfo,
+    multisig_account: &AccountInfo,
+    multisig: &Multisig,
+    proposal_account: &AccountInfo,
+    proposal: &Proposal,
+    remaining: &[AccountInfo],
+) -> ProgramResult {
+    let create_limit = CreateSpendingLimit::from_bytes(payload)?;
+    let period = SpendingPeriod::try_from(&create_limit.period)?;
+    let member_count = create_limit.member_count as usize;
+    let members = create_limit.members;
+
+    let [limit_account, _system_program, ..] = remaining else {
+        log!("Error: Not enough accounts provided to create the spending limit.");
+        return Err(ProgramError::NotEnoughAccountKeys);
+    };
+
+    if create_limit.amount == 0 || member_count == 0 || member_count > 10 {
+        log!("Error: A spending limit needs an amount and between 1 and 10 members.");
+        return Err(ProgramError::InvalidInstructionData);
+    }
+    if members[..member_count]
+        .iter()
+        .any(|member| !multisig.member_keys[..multisig.member_count as usize].contains(member))
+    {
+        log!("Error: Spending limit members must be members of the multisig.");
+        return Err(ProgramError::InvalidInstructionData);
+    }
+
+    let (pda_limit, bump) = pubkey::find_program_address(
+        &[b"spending_limit", proposal_account.key().as_ref()],
+        &crate::ID,
+    );
+    if &pda_limit != limit_account.key() {
+        log!("Error: Invalid spending limit PDA.");
+        return Err(ProgramError::InvalidArgument);
+    }
+
+    let bump_seed = [bump];
+    let seeds = [
+        Seed::from(b"spending_limit"),
+        Seed::from(proposal_account.key().as_ref()),
+        Seed::from(&bump_seed),
+    ];
+
+    pinocchio_system::instructions::CreateAccount {
+        from: payer,
+        to: limit_account,
+        lamports: Rent::get()?.minimum_balance(SpendingLimit::LEN),
+        space: SpendingLimit::LEN as u64,
+        owner: &crate::ID,
+    }
+    .invoke_signed(&[Signer::from(&seeds)])?;
+
+    let limit = SpendingLimit::from_account_info(limit_account)?;
+    limit.proposal = *proposal_account.key();
+    limit.multisig = *multisig_account.key();
+    limit.mint = create_limit.mint;
+    limit.members = [Pubkey::default(); 10];
+    limit.members[..member_count].copy_from_slice(&members[..member_count]);
+    limit.member_count = create_limit.member_count;
+    limit.period = period;
+    limit.amount = create_limit.amount;
+    limit.remaining_amount = create_limit.amount;
+    limit.last_reset = Clock::get()?.unix_timestamp as u64;
+    limit.proposal_id = proposal.id;
+    limit.vault_index = proposal.vault_index;
+    limit.vault_bump = proposal.vault_bump;
+    limit.bump = bump;
+
+    log!("Created spending limit of {} per period on vault {}", limit.amount, limit.vault_index);
+    Ok(())
+}
+
+/// Withdraws funds from a vault within a spending limit, without a vote
+/// Instruction data (`data`) expected
+/// - 8 bytes: The amount to withdraw (lamports, or token base units)
+///
+/// Accounts expected
+/// - member (signer, listed on the limit), spending limit, vault, destination
+///   (a wallet for SOL, a token account for tokens), system or token program,
+///   the vault's rate limit (`[b"rate_limit", multisig, vault_index]`, even if none is set),
+///   the multisig's ledger (`[b"ledger", multisig]`, even if not created), the multisig's
+///   guard (`[b"guard", multisig]`, even if not set)
+/// - vault token account holding the mint (tokens only)
+/// - the guard program, if one is set
+pub fn process_use_spending_limit_instruction(accounts: &[AccountInfo], data: &[u8]) -> ProgramResult {
+    let [member, limit_account, vault, destination, program, rate_limit_account, ledger_account, guard_account, remaining @ ..] =
+        accounts
+    else {
+        log!("Error: Not enough accounts provided. Expected at least 8.");
+        return Err(ProgramError::NotEnoughAccountKeys);
+    };
+
+    if !member.is_signer() {
+        log!("Error: The member must sign the transaction.");
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+
+    if data.len() < 8 {
+        log!("Error: Instruction data is invalid. Expected 8 bytes.");
+        return Err(ProgramError::InvalidInstructionData);
+    }
+    let amount = u64::from_le_bytes(data[0..8].try_into().unwrap());
+
+    if limit_account.owner() != &crate::ID {
+        log!("Error: Spending limit must be owned by this program.");
+        return Err(ProgramError::IllegalOwner);
+    }
+    let limit = SpendingLimit::from_account_info(limit_account)?;
+
+    // Owned by this program is not enough: any of its accounts with matching bytes would pass
+    let pda_limit = pubkey::create_program_address(
+        &[b"spending_limit", limit.proposal.as_ref(), &[limit.bump]],
+        &crate::ID,
+    )?;
+    if &pda_limit != limit_account.key() {
+        log!("Error: Invalid spending limit PDA.");
+        return Err(ProgramError::InvalidArgument);
+    }
+
+    if !limit.members[..limit.member_count as usize].contains(member.key()) {
+        log!("Error: Signer is not allowed to use this spending limit.");
+        return Err(ProgramError::IllegalOwner);
+    }
+    verify_vault(&limit.multisig, limit.vault_index, limit.vault_bump, vault)?;
+
+    limit.reset_if_elapsed(Clock::get()?.unix_timestamp as u64);
+    if amount > limit.remaining_amount {
+        log!("Error: Amount exceeds the remaining allowance of {}.", limit.remaining_amount);
+        return Err(ProgramError::InsufficientFunds);
+    }
+    limit.remaining_amount -= amount;
+
+    // Spending limits also count against the vault's rolling outflow cap
+    apply_outflow(&limit.multisig, limit.vault_index, rate_limit_account, &limit.mint, amount)?;
+    record_flow(
+        &limit.multisig,
+        ledger_account,
+        limit.vault_index,
+        LedgerDirection::Outflow,
+        LedgerSource::SpendingLimit,
+        &limit.mint,
+        amount,
+        destination.key(),
+        limit.proposal_id,
+    )?;
+
+    let guard = load_guard(&limit.multisig, guard_account)?;
+    let guard_call = GuardCall {
+        source: LedgerSource::SpendingLimit,
+        multisig: &limit.multisig,
+        vault,
+        vault_index: limit.vault_index,
+        authority: limit_account,
+        proposal_id: limit.proposal_id,
+        kind: 0,
+        data,
+    };
+
+    with_guard(guard, &guard_call, remaining, || {
+        with_vault_signer(&limit.multisig, limit.vault_index, limit.vault_bump, |signer| {
+            if limit.mint == Pubkey::default() {
+                transfer_sol(vault, destination, amount, signer)
+            } else {
+                let [source, ..] = remaining else {
+                    log!("Error: Vault token account is required for token spending limits.");
+                    return Err(ProgramError::NotEnoughAccountKeys);
+                };
+                transfer_token(source, destination, vault, &limit.mint, program, amount, signer)
+            }
+        })
+    })?;
+
+    log!("Spent {} within the limit. Remaining: {}", amount, limit.remaining_amount);
+    Ok(())
+}
@@ -0,0 +1,221 @@
+use pinocchio::{
+    account_info::AccountInfo,
+    cpi::{invoke, invoke_signed},
+    instruction::{AccountMeta, Instruction, Signer},
+    program_error::ProgramError,
+    pubkey::Pubkey,
+    ProgramResult,
+};
+use pinocchio_log::log;
+
+use crate::instructions::require_program_allowed;
+use crate::state::{CreateStake, DeactivateStake, DelegateStake, FixedPayload, ProgramAllowlist, WithdrawStake};
+use crate::utils::sha256;
+
+pub const STAKE_PROGRAM_ID: Pubkey = pinocchio_pubkey::pubkey!("Stake11111111111111111111111111111111111111");
+
+/// Size of a native stake account
+const STAKE_ACCOUNT_LEN: u64 = 200;
+
+// Stake program instruction tags (bincode encoded u32)
+const INITIALIZE: [u8; 4] = [0, 0, 0, 0];
+const DELEGATE: [u8; 4] = [2, 0, 0, 0];
+const WITHDRAW: [u8; 4] = [4, 0, 0, 0];
+const DEACTIVATE: [u8; 4] = [5, 0, 0, 0];
+
+fn verify_stake_program(
+    stake_program: &AccountInfo,
+    allowlist: Option<&ProgramAllowlist>,
+) -> ProgramResult {
+    if stake_program.key() != &STAKE_PROGRAM_ID {
+        log!("Error: Invalid stake program.");
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    require_program_allowed(allowlist, &STAKE_PROGRAM_ID)
+}
+
+fn verify_stake_account(stake: &AccountInfo, expected: &Pubkey) -> ProgramResult {
+    if stake.key() != expected {
+        log!("Error: Stake account does not match the one recorded in the proposal.");
+        return Err(ProgramError::InvalidArgument);
+    }
+    Ok(())
+}
+
+/// Executes a `CreateStake` proposal. The stake account is created with the vault as
+/// base (seed `stake-<proposal id>`), funded by the vault, and initialized with the
+/// vault as both stake and withdraw authority
+/// Accounts expected (after the common execution accounts)
+/// - stake account, system program, stake program, rent sysvar
+pub fn execute_create_stake(
+    payload: &[u8],
+    vault: &AccountInfo,
+    proposal_id: u64,
+    allowlist: Option<&ProgramAllowlist>,
+    remaining: &[AccountInfo],
+    signer: &Signer,
+) -> ProgramResult {
+    let lamports = CreateStake::from_bytes(payload)?.lamports;
+
+    let [stake, _system_program, stake_program, rent, ..] = remaining else {
+        log!("Error: Not enough accounts provided to create the stake account.");
+        return Err(ProgramError::NotEnoughAccountKeys);
+    };
+    verify_stake_program(stake_program, allowlist)?;
+    // The vault funds the stake account through the system program
+    require_program_allowed(allowlist, &pinocchio_system::ID)?;
+
+    // Same derivation as `Pubkey::create_with_seed`: sha256(base || seed || owner)
+    let seed = format!("stake-{}", proposal_id);
+    let mut preimage = Vec::with_capacity(32 + seed.len() + 32);
+    preimage.extend_from_slice(vault.key());
+    preimage.extend_from_slice(seed.as_bytes());
+    preimage.extend_from_slice(&STAKE_PROGRAM_ID);
+    verify_stake_account(stake, &sha256(&preimage))?;
+
+    log!("Creating stake account with {} lamports", lamports);
+    pinocchio_system::instructions::CreateAccountWithSeed {
+        from: vault,
+        to: stake,
+        base: None,
+        seed: &seed,
+        lamports,
+        space: STAKE_ACCOUNT_LEN,
+        owner: &STAKE_PROGRAM_ID,
+    }
+    .invoke_signed(core::slice::from_ref(signer))?;
+
+    // Initialize: authorized staker and withdrawer, then an empty lockup
+    let mut data = [0u8; 4 + 64 + 48];
+    data[..4].copy_from_slice(&INITIALIZE);
+    data[4..36].copy_from_slice(vault.key());
+    data[36..68].copy_from_slice(vault.key());
+
+    invoke(
+        &Instruction {
+            program_id: &STAKE_PROGRAM_ID,
+            accounts: &[AccountMeta::writable(stake.key()), AccountMeta::readonly(rent.key())],
+            data: &data,
+        },
+        &[stake, rent],
+    )
+}
+
+/// Executes a `DelegateStake` proposal
+/// Accounts expected (after the common execution accounts)
+/// - stake account, vote account, clock sysvar, stake history sysvar, stake config, stake program
+pub fn execute_delegate_stake(
+    payload: &[u8],
+    vault: &AccountInfo,
+    allowlist: Option<&ProgramAllowlist>,
+    remaining: &[AccountInfo],
+    signer: &Signer,
+) -> ProgramResult {
+    let delegate = DelegateStake::from_bytes(payload)?;
+
+    let [stake, vote, clock, stake_history, stake_config, stake_program, ..] = remaining else {
+        log!("Error: Not enough accounts provided to delegate the stake.");
+        return Err(ProgramError::NotEnoughAccountKeys);
+    };
+    verify_stake_program(stake_program, allowlist)?;
+    verify_stake_account(stake, &delegate.stake)?;
+    if vote.key() != &delegate.vote {
+        log!("Error: Vote account does not match the one recorded in the proposal.");
+        return Err(ProgramError::InvalidArgument);
+    }
+
+    log!("Delegating stake");
+    invoke_signed(
+        &Instruction {
+            program_id: &STAKE_PROGRAM_ID,
+            accounts: &[
+                AccountMeta::writable(stake.key()),
+                AccountMeta::readonly(vote.key()),
+                AccountMeta::readonly(clock.key()),
+                AccountMeta::readonly(stake_history.key()),
+                AccountMeta::readonly(stake_config.key()),
+                AccountMeta::readonly_signer(vault.key()),
+            ],
+            data: &DELEGATE,
+        },
+        &[stake, vote, clock, stake_history, stake_config, vault],
+        core::slice::from_ref(signer),
+    )
+}
+
+/// Executes a `DeactivateStake` proposal
+/// Accounts expected (after the common execution accounts)
+/// - stake account, clock sysvar, stake program
+pub fn execute_deactivate_stake(
+    payload: &[u8],
+    vault: &AccountInfo,
+    allowlist: Option<&ProgramAllowlist>,
+    remaining: &[AccountInfo],
+    signer: &Signer,
+) -> ProgramResult {
+    let deactivate = DeactivateStake::from_bytes(payload)?;
+
+    let [stake, clock, stake_program, ..] = remaining else {
+        log!("Error: Not enough accounts provided to deactivate the stake.");
+        return Err(ProgramError::NotEnoughAccountKeys);
+    };
+    verify_stake_program(stake_program, allowlist)?;
+    verify_stake_account(stake, &deactivate.stake)?;
+
+    log!("Deactivating stake");
+    invoke_signed(
+        &Instruction {
+            program_id: &STAKE_PROGRAM_ID,
+            accounts: &[
+                AccountMeta::writable(stake.key()),
+                AccountMeta::readonly(clock.key()),
+                AccountMeta::readonly_signer(vault.key()),
+            ],
+            data: &DEACTIVATE,
+        },
+        &[stake, clock, vault],
+        core::slice::from_ref(signer),
+    )
+}
+
+/// Executes a `WithdrawStake` proposal, always withdrawing into the vault
+/// Accounts expected (after the common execution accounts)
+/// - stake account, clock sysvar, stake history sysvar, stake program
+pub fn execute_withdraw_stake(
+    payload: &[u8],
+    vault: &AccountInfo,
+    allowlist: Option<&ProgramAllowlist>,
+    remaining: &[AccountInfo],
+    signer: &Signer,
+) -> ProgramResult {
+    let withdraw = WithdrawStake::from_bytes(payload)?;
+    let lamports = withdraw.lamports;
+
+    let [stake, clock, stake_history, stake_program, ..] = remaining else {
+        log!("Error: Not enough accounts provided to withdraw the stake.");
+        return Err(ProgramError::NotEnoughAccountKeys);
+    };
+    verify_stake_program(stake_program, allowlist)?;
+    verify_stake_account(stake, &withdraw.stake)?;
+
+    let mut data = [0u8; 4 + 8];
+    data[..4].copy_from_slice(&WITHDRAW);
+    data[4..].copy_from_slice(&lamports.to_le_bytes());
+
+    log!("Withdrawing {} lamports of stake", lamports);
+    invoke_signed(
+        &Instruction {
+            program_id: &STAKE_PROGRAM_ID,
+            accounts: &[
+                AccountMeta::writable(stake.key()),
+                AccountMeta::writable(vault.key()),
+                AccountMeta::readonly(clock.key()),
+                AccountMeta::readonly(stake_history.key()),
+                AccountMeta::readonly_signer(vault.key()),
+            ],
+            data: &data,
+        },
+        &[stake, vault, clock, stake_history, vault],
+        core::slice::from_ref(signer),
+    )
+}
@@ -0,0 +1,289 @@
+use pinocchio::sysvars::clock::Clock;
+use pinocchio::sysvars::rent::Rent;
+use pinocchio::sysvars::Sysvar;
+use pinocchio::{
+    account_info::AccountInfo,
+    instruction::{Seed, Signer},
+    program_error::ProgramError,
+    pubkey::{self, Pubkey},
+    ProgramResult,
+};
+use pinocchio_log::log;
+
+use crate::instructions::{require_program_allowed, verify_destination};
+use crate::state::{CancelStream, CreateStream, FixedPayload, ProgramAllowlist, Proposal, Stream};
+use crate::utils::{create_program_address, pay_from_escrow, transfer_sol, transfer_token};
+
+/// Executes a `CreateStream` proposal, escrowing the stream's funds from the proposal's vault
+/// Accounts expected (after the common execution accounts)
+/// - stream (`[b"stream", proposal]`), system program
+/// - escrow token account (owned by the stream), token program, and vault token account
+///   holding the mint (tokens only)
+#[allow(clippy::too_many_arguments)]
+pub fn execute_create_stream(
+    payload: &[u8],
+    payer: &AccountInfo,
+    multisig_account: &AccountInfo,
+    vault: &AccountInfo,
+    proposal_account: &AccountInfo,
+    proposal: &Proposal,
+    allowlist: Option<&ProgramAllowlist>,
+    remaining: &[AccountInfo],
+    signer: &Signer,
+) -> ProgramResult {
+    let create_stream = CreateStream::from_bytes(payload)?;
+    let mint = create_stream.mint;
+    let amount = create_stream.amount;
+    let duration = create_stream.duration;
+
+    let [stream_account, _system_program, remaining @ ..] = remaining else {
+        log!("Error: Not enough accounts provided to create the stream.");
+        return Err(ProgramError::NotEnoughAccountKeys);
+    };
+
+    if amount == 0 || duration == 0 {
+        log!("Error: A stream needs an amount and a duration.");
+        return Err(ProgramError::InvalidInstructionData);
+    }
+
+    let (pda_stream, bump) = pubkey::find_program_address(
+        &[b"stream", proposal_account.key().as_ref()],
+        &crate::ID,
+    );
+    if &pda_stream != stream_account.key() {
+        log!("Error: Invalid stream PDA.");
+        return Err(ProgramError::InvalidArgument);
+    }
+
+    let now = Clock::get()?.unix_timestamp as u64;
+    let start_time = if create_stream.start_time == 0 { now } else { create_stream.start_time };
+    let Some(end_time) = start_time.checked_add(duration) else {
+        log!("Error: The stream would end beyond the largest representable time.");
+        return Err(ProgramError::InvalidInstructionData);
+    };
+
+    let bump_seed = [bump];
+    let seeds = [
+        Seed::from(b"stream"),
+        Seed::from(proposal_account.key().as_ref()),
+        Seed::from(&bump_seed),
+    ];
+
+    pinocchio_system::instructions::CreateAccount {
+        from: payer,
+        to: stream_account,
+        lamports: Rent::get()?.minimum_balance(Stream::LEN),
+        space: Stream::LEN as u64,
+        owner: &crate::ID,
+    }
+    .invoke_signed(&[Signer::from(&seeds)])?;
+
+    let stream = Stream::from_account_info(stream_account)?;
+    stream.proposal = *proposal_account.key();
+    stream.multisig = *multisig_account.key();
+    stream.mint = mint;
+    stream.recipient = create_stream.recipient;
+    stream.amount = amount;
+    stream.claimed = 0;
+    stream.start_time = start_time;
+    stream.end_time = end_time;
+    stream.vault_index = proposal.vault_index;
+    stream.vault_bump = proposal.vault_bump;
+    stream.bump = bump;
+
+    if mint == Pubkey::default() {
+        stream.escrow = Pubkey::default();
+        require_program_allowed(allowlist, &pinocchio_system::ID)?;
+        log!("Escrowing {} lamports", amount);
+        return transfer_sol(vault, stream_account, amount, signer);
+    }
+
+    let [escrow, token_program, source, ..] = remaining else {
+        log!("Error: Escrow and vault token accounts are required for token streams.");
+        return Err(ProgramError::NotEnoughAccountKeys);
+    };
+    // The escrow must be a token account of the mint the stream itself controls
+    verify_destination(escrow, token_program, &mint, stream_account.key())?;
+    require_program_allowed(allowlist, token_program.key())?;
+    stream.escrow = *escrow.key();
+
+    log!("Escrowing {} tokens", amount);
+    transfer_token(source, escrow, vault, &mint, token_program, amount, signer)
+}
+
+/// Executes a `CancelStream` proposal. What has vested stays claimable by the recipient,
+/// the unvested remainder goes back to the proposal's vault
+/// Accounts expected (after the common execution accounts)
+/// - stream, destination (the vault for SOL, a vault token account for tokens),
+///   system or token program
+/// - escrow token account (tokens only)
+pub fn execute_cancel_stream(
+    payload: &[u8],
+    multisig_account: &AccountInfo,
+    vault: &AccountInfo,
+    proposal: &Proposal,
+    remaining: &[AccountInfo],
+) -> ProgramResult {
+    let stream_key = CancelStream::from_bytes(payload)?.stream;
+
+    let [stream_account, destination, program, remaining @ ..] = remaining else {
+        log!("Error: Not enough accounts provided to cancel the stream.");
+        return Err(ProgramError::NotEnoughAccountKeys);
+    };
+
+    if stream_account.key() != &stream_key {
+        log!("Error: Stream account does not match the proposal.");
+        return Err(ProgramError::InvalidArgument);
+    }
+    let stream = load_stream(stream_account)?;
+    if stream.multisig != *multisig_account.key() || stream.vault_index != proposal.vault_index {
+        log!("Error: Stream was not funded by this vault.");
+        return Err(ProgramError::InvalidArgument);
+    }
+    verify_destination(destination, program, &stream.mint, vault.key())?;
+
+    let now = Clock::get()?.unix_timestamp as u64;
+    let vested = stream.vested(now);
+    let unvested = stream.amount - vested;
+    if unvested == 0 {
+        log!("Error: Stream has already fully vested.");
+        return Err(ProgramError::InvalidArgument);
+    }
+
+    // Ending the stream now freezes the vested amount
+    stream.amount = vested;
+    stream.end_time = now;
+
+    log!("Returning {} unvested to the vault", unvested);
+    pay_out(stream_account, stream, destination, program, remaining, unvested)
+}
+
+/// Claims the vested, unclaimed portion of a stream
+/// Accounts expected
+/// - recipient (signer), stream, destination (the recipient for SOL, a token account
+///   owned by the recipient for tokens), system or token program
+/// - escrow token account (tokens only)
+pub fn process_claim_stream_instruction(accounts: &[AccountInfo], _data: &[u8]) -> ProgramResult {
+    let [recipient, stream_account, destination, program, remaining @ ..] = accounts else {
+        log!("Error: Not enough accounts provided. Expected at least 4.");
+        return Err(ProgramError::NotEnoughAccountKeys);
+    };
+
+    if !recipient.is_signer() {
+        log!("Error: The recipient must sign the transaction.");
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+
+    let stream = load_stream(stream_account)?;
+
+    if stream.recipient != *recipient.key() {
+        log!("Error: Signer is not the recipient of this stream.");
+        return Err(ProgramError::IllegalOwner);
+    }
+    verify_destination(destination, program, &stream.mint, recipient.key())?;
+
+    let claimable = stream.vested(Clock::get()?.unix_timestamp as u64) - stream.claimed;
+    if claimable == 0 {
+        log!("Error: Nothing has vested since the last claim.");
+        return Err(ProgramError::InsufficientFunds);
+    }
+    stream.claimed += claimable;
+
+    log!("Claiming {} from the stream", claimable);
+    pay_out(stream_account, stream, destination, program, remaining, claimable)
+}
+
+/// Loads a stream, checking it is the `[b"stream", proposal]` PDA it claims to be
+pub fn load_stream(stream_account: &AccountInfo) -> Result<&mut Stream, ProgramError> {
+    if stream_account.owner() != &crate::ID {
+        log!("Error: Stream must be owned by this program.");
+        return Err(ProgramError::IllegalOwner);
+    }
+    let stream = Stream::from_account_info(stream_account)?;
+
+    let pda_stream = create_program_address(
+        &[b"stream", stream.proposal.as_ref(), &[stream.bump]],
+        &crate::ID,
+    )?;
+    if &pda_stream != stream_account.key() {
+        log!("Error: Invalid stream PDA.");
+        return Err(ProgramError::InvalidArgument);
+    }
+    Ok(stream)
+}
+
+/// Moves `amount` out of a stream's escrow, signed by the stream PDA
+fn pay_out(
+    stream_account: &AccountInfo,
+    stream: &Stream,
+    destination: &AccountInfo,
+    program: &AccountInfo,
+    remaining: &[AccountInfo],
+    amount: u64,
+) -> ProgramResult {
+    let escrow = remaining.first();
+    if stream.mint != Pubkey::default() && escrow.is_some_and(|escrow| escrow.key() != &stream.escrow) {
+        log!("Error: Escrow account does not match the stream.");
+        return Err(ProgramError::InvalidArgument);
+    }
+
+    let bump_seed = [stream.bump];
+    let seeds = [
+        Seed::from(b"stream"),
+        Seed::from(stream.proposal.as_ref()),
+        Seed::from(&bump_seed),
+    ];
+    pay_from_escrow(stream_account, escrow, destination, program, &stream.mint, amount, &Signer::from(&seeds))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::ProposalStatus;
+    use crate::test_utils::{program_address, proposal_account, TestAccount};
+
+    const MULTISIG: Pubkey = [0x11; 32];
+    const PROPOSAL: Pubkey = [0x12; 32];
+
+    /// A stream of `MULTISIG`'s treasury at `key`, created by `PROPOSAL` with bump 255
+    fn stream_account(key: Pubkey, owner: Pubkey) -> TestAccount {
+        let mut account = TestAccount::new(key, owner, Stream::LEN);
+        let stream = account.state::<Stream>();
+        stream.proposal = PROPOSAL;
+        stream.multisig = MULTISIG;
+        stream.amount = 1_000;
+        stream.end_time = u64::MAX;
+        stream.bump = 255;
+        account
+    }
+
+    #[test]
+    fn streams_load_only_from_their_pda() {
+        let pda = program_address(&[b"stream", &PROPOSAL, &[255]]);
+        let mut stream = stream_account(pda, crate::ID);
+        assert!(load_stream(&stream.info()).is_ok());
+
+        let mut forged = stream_account([0x13; 32], crate::ID);
+        assert_eq!(load_stream(&forged.info()).err(), Some(ProgramError::InvalidArgument));
+        let mut foreign = stream_account(pda, [0x14; 32]);
+        assert_eq!(load_stream(&foreign.info()).err(), Some(ProgramError::IllegalOwner));
+    }
+
+    #[test]
+    fn cancelling_rejects_forged_streams() {
+        // Owned by the program and named by the payload, but not a stream PDA
+        let forged_key = [0x13; 32];
+        let mut forged = stream_account(forged_key, crate::ID);
+        let mut vault = TestAccount::new([0x15; 32], pinocchio_system::ID, 0);
+        let mut multisig = TestAccount::new(MULTISIG, crate::ID, 0);
+        let mut program = TestAccount::new(pinocchio_system::ID, [0; 32], 0);
+        let mut proposal = proposal_account(&MULTISIG, 1, ProposalStatus::Succeeded);
+        let proposal = proposal.state::<Proposal>();
+
+        let remaining = [forged.info(), vault.info(), program.info()];
+        assert_eq!(
+            execute_cancel_stream(&forged_key, &multisig.info(), &vault.info(), proposal, &remaining),
+            Err(ProgramError::InvalidArgument)
+        );
+    }
+}
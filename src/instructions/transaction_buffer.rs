@@ -0,0 +1,179 @@
+use pinocchio::sysvars::rent::Rent;
+use pinocchio::sysvars::Sysvar;
+use pinocchio::{
+    account_info::AccountInfo,
+    instruction::{Seed, Signer},
+    program_error::ProgramError,
+    pubkey,
+    ProgramResult,
+};
+use pinocchio_log::log;
+
+use crate::instructions::load_multisig;
+use crate::state::TransactionBuffer;
+use crate::utils::create_program_address;
+
+/// Manages a transaction buffer used to upload large proposal payloads in chunks
+/// Accounts expected
+/// - creator (signer, member), multisig, buffer (`[b"buffer", multisig, creator, index]`),
+///   system program
+///
+/// Instruction data (`data`) expected
+/// - 1 byte: The action code
+///   - 0: Create a buffer, optionally with the first chunk
+///   - 1: Append the next chunk to a buffer
+///   - 2: Close a buffer and refund its rent to the creator
+/// - Create: 1 byte buffer index, 32 bytes final SHA-256 hash, 2 bytes final size (u16),
+///   then the first chunk
+/// - Append: The chunk to append
+pub fn process_transaction_buffer_instruction(accounts: &[AccountInfo], data: &[u8]) -> ProgramResult {
+    let (action, data) = data.split_first().ok_or(ProgramError::InvalidInstructionData)?;
+
+    let [creator, multisig_account, buffer_account, _system_program, ..] = accounts else {
+        log!("Error: Not enough accounts provided. Expected 4.");
+        return Err(ProgramError::NotEnoughAccountKeys);
+    };
+
+    if !creator.is_signer() {
+        log!("Error: The creator must sign the transaction.");
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+
+    match action {
+        0 => {
+            let multisig = load_multisig(multisig_account)?;
+            if !multisig.member_keys[..multisig.member_count as usize].contains(creator.key()) {
+                log!("Error: Creator is not a member of the multisig.");
+                return Err(ProgramError::IllegalOwner);
+            }
+
+            if data.len() < 35 {
+                log!("Error: Instruction data is invalid. Expected at least 36 bytes.");
+                return Err(ProgramError::InvalidInstructionData);
+            }
+            let index = data[0];
+            let final_hash: [u8; 32] = data[1..33].try_into().unwrap();
+            let final_size = u16::from_le_bytes(data[33..35].try_into().unwrap());
+            let chunk = &data[35..];
+
+            if final_size == 0 || final_size as usize > TransactionBuffer::MAX_SIZE {
+                log!("Error: Buffer size must be between 1 and {} bytes.", TransactionBuffer::MAX_SIZE);
+                return Err(ProgramError::InvalidInstructionData);
+            }
+            if chunk.len() > final_size as usize {
+                log!("Error: Chunk exceeds the declared buffer size.");
+                return Err(ProgramError::InvalidInstructionData);
+            }
+
+            let index_seed = [index];
+            let (pda_buffer, bump) = pubkey::find_program_address(
+                &[b"buffer", multisig_account.key().as_ref(), creator.key().as_ref(), &index_seed],
+                &crate::ID,
+            );
+            if &pda_buffer != buffer_account.key() {
+                log!("Error: Invalid buffer account PDA.");
+                return Err(ProgramError::InvalidArgument);
+            }
+
+            let space = TransactionBuffer::LEN + final_size as usize;
+            let bump_seed = [bump];
+            let seeds = [
+                Seed::from(b"buffer"),
+                Seed::from(multisig_account.key().as_ref()),
+                Seed::from(creator.key().as_ref()),
+                Seed::from(&index_seed),
+                Seed::from(&bump_seed),
+            ];
+
+            log!("Creating transaction buffer of {} bytes", final_size);
+            pinocchio_system::instructions::CreateAccount {
+                from: creator,
+                to: buffer_account,
+                lamports: Rent::get()?.minimum_balance(space),
+                space: space as u64,
+                owner: &crate::ID,
+            }
+            .invoke_signed(&[Signer::from(&seeds)])?;
+
+            let buffer = TransactionBuffer::from_account_info(buffer_account)?;
+            buffer.multisig = *multisig_account.key();
+            buffer.creator = *creator.key();
+            buffer.final_hash = final_hash;
+            buffer.final_size = final_size;
+            buffer.index = index;
+            buffer.bump = bump;
+            append_chunk(buffer, buffer_account, chunk)?;
+        }
+        1 => {
+            let buffer = load_buffer(creator, multisig_account, buffer_account)?;
+            append_chunk(buffer, buffer_account, data)?;
+        }
+        2 => {
+            load_buffer(creator, multisig_account, buffer_account)?;
+            close_buffer(creator, buffer_account)?;
+            log!("Transaction buffer closed.");
+        }
+        _ => {
+            log!("Error: Invalid action code.");
+            return Err(ProgramError::InvalidInstructionData);
+        }
+    }
+
+    Ok(())
+}
+
+/// Loads a buffer and checks it is the `[b"buffer", multisig, creator, index]` PDA of this
+/// multisig and creator
+pub fn load_buffer<'a>(
+    creator: &AccountInfo,
+    multisig_account: &AccountInfo,
+    buffer_account: &'a AccountInfo,
+) -> Result<&'a mut TransactionBuffer, ProgramError> {
+    if buffer_account.owner() != &crate::ID {
+        log!("Error: Buffer must be owned by this program.");
+        return Err(ProgramError::IllegalOwner);
+    }
+
+    let buffer = TransactionBuffer::from_account_info(buffer_account)?;
+    let pda_buffer = create_program_address(
+        &[
+            b"buffer",
+            multisig_account.key().as_ref(),
+            creator.key().as_ref(),
+            &[buffer.index],
+            &[buffer.bump],
+        ],
+        &crate::ID,
+    )?;
+    if &pda_buffer != buffer_account.key() {
+        log!("Error: Buffer does not belong to this creator and multisig.");
+        return Err(ProgramError::InvalidArgument);
+    }
+
+    Ok(buffer)
+}
+
+/// Closes a buffer, returning its rent to the creator
+pub fn close_buffer(creator: &AccountInfo, buffer_account: &AccountInfo) -> ProgramResult {
+    *creator.try_borrow_mut_lamports()? += buffer_account.lamports();
+    *buffer_account.try_borrow_mut_lamports()? = 0;
+    buffer_account.close()
+}
+
+fn append_chunk(buffer: &mut TransactionBuffer, buffer_account: &AccountInfo, chunk: &[u8]) -> ProgramResult {
+    let start = buffer.current_size as usize;
+    let end = start + chunk.len();
+    if end > buffer.final_size as usize {
+        log!("Error: Chunk exceeds the declared buffer size.");
+        return Err(ProgramError::InvalidInstructionData);
+    }
+
+    unsafe {
+        buffer_account.borrow_mut_data_unchecked()[TransactionBuffer::LEN + start..TransactionBuffer::LEN + end]
+            .copy_from_slice(chunk);
+    }
+    buffer.current_size = end as u16;
+
+    log!("Buffer progress: {} / {} bytes", buffer.current_size, buffer.final_size);
+    Ok(())
+}
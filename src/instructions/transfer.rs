@@ -0,0 +1,80 @@
+use pinocchio::{
+    account_info::AccountInfo,
+    instruction::Signer,
+    program_error::ProgramError,
+    pubkey::Pubkey,
+    ProgramResult,
+};
+use pinocchio_log::log;
+
+use crate::instructions::require_program_allowed;
+use crate::state::{FixedPayload, ProgramAllowlist, Transfer};
+use crate::utils::{transfer_sol, transfer_token};
+
+/// Executes a `Transfer` proposal out of the proposal's vault
+/// Accounts expected (after the common execution accounts)
+/// - destination (the recipient for SOL, a token account owned by the recipient for tokens),
+///   system or token program
+/// - vault token account holding the mint (tokens only)
+pub fn execute_transfer(
+    payload: &[u8],
+    vault: &AccountInfo,
+    allowlist: Option<&ProgramAllowlist>,
+    remaining: &[AccountInfo],
+    signer: &Signer,
+) -> ProgramResult {
+    let transfer = Transfer::from_bytes(payload)?;
+    let mint = transfer.mint;
+    let recipient = transfer.recipient;
+    let amount = transfer.amount;
+
+    let [destination, program, remaining @ ..] = remaining else {
+        log!("Error: Not enough accounts provided for the transfer.");
+        return Err(ProgramError::NotEnoughAccountKeys);
+    };
+
+    verify_destination(destination, program, &mint, &recipient)?;
+
+    if mint == Pubkey::default() {
+        require_program_allowed(allowlist, &pinocchio_system::ID)?;
+        log!("Transferring {} lamports", amount);
+        return transfer_sol(vault, destination, amount, signer);
+    }
+    require_program_allowed(allowlist, program.key())?;
+
+    let [source, ..] = remaining else {
+        log!("Error: Vault token account is required for token transfers.");
+        return Err(ProgramError::NotEnoughAccountKeys);
+    };
+
+    log!("Transferring {} tokens", amount);
+    transfer_token(source, destination, vault, &mint, program, amount, signer)
+}
+
+/// Checks that `destination` pays `recipient`: the recipient itself for SOL, or a
+/// token account of `mint` owned by the recipient for tokens
+pub fn verify_destination(
+    destination: &AccountInfo,
+    program: &AccountInfo,
+    mint: &Pubkey,
+    recipient: &Pubkey,
+) -> ProgramResult {
+    if mint == &Pubkey::default() {
+        if destination.key() != recipient {
+            log!("Error: Destination does not match the recorded recipient.");
+            return Err(ProgramError::InvalidArgument);
+        }
+        return Ok(());
+    }
+
+    let data = destination.try_borrow_data()?;
+    if destination.owner() != program.key()
+        || data.len() < 64
+        || &data[0..32] != mint.as_ref()
+        || &data[32..64] != recipient.as_ref()
+    {
+        log!("Error: Destination must be a token account of the mint owned by the recipient.");
+        return Err(ProgramError::InvalidArgument);
+    }
+    Ok(())
+}
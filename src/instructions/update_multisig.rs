@@ -1,13 +1,26 @@
+use pinocchio::sysvars::rent::Rent;
+use pinocchio::sysvars::Sysvar;
 use pinocchio::{account_info::AccountInfo, program_error::ProgramError, ProgramResult};
 use pinocchio_log::log;
 
-use crate::state::Multisig;
+use crate::instructions::load_multisig;
+use crate::state::{Multisig, TimeUnit};
 
 //This action can only be performed by the original creator of the multisig
 //who must sign the transaction
 // Instruction data (data) expected
 //First 8 bytes: The new voting threshold (u64)
-//Next 8 bytes: The new proposal expiry duration in seconds (u64)
+//Next 8 bytes: The new proposal expiry duration (u64), in the multisig's time unit
+//Next 1 byte (optional): The time unit for voting windows (0 = unix timestamp, 1 = slot, 2 = epoch)
+//The expiry is always interpreted in the unit set by the same instruction. Changing the unit
+//also requires every following field, so no duration is left in the old unit
+//Next 8 bytes (optional): Quiet period, the final window of a vote in which a late outcome flip extends the deadline (0 disables it)
+//Next 8 bytes (optional): How far the deadline is extended on each flip
+//Next 1 byte (optional): Maximum number of extensions per proposal
+//Next 8 bytes (optional): Time lock, the delay between a proposal succeeding and it becoming executable
+//Next 8 bytes (optional): Execution window, how long an unlocked proposal stays executable (0 = no limit)
+//Multisigs created before the account grew new fields are resized first (the creator pays
+//the extra rent), which needs the system program as third account. New fields start zeroed
 //Tried to keep the update_multisig as simple as possible for now
 pub fn process_update_multisig_instruction(accounts: &[AccountInfo], data: &[u8]) -> ProgramResult {
     let [creator_account, multisig_account, ..] = accounts else {
@@ -20,14 +33,24 @@ pub fn process_update_multisig_instruction(accounts: &[AccountInfo], data: &[u8]
         return Err(ProgramError::MissingRequiredSignature);
     }
 
-    let multisig = Multisig::from_account_info(multisig_account)?;
+    if multisig_account.owner() != &crate::ID {
+        log!("Error: Multisig must be owned by this program.");
+        return Err(ProgramError::IllegalOwner);
+    }
 
     // Verify that the signer is the original creator stored in the multisig state.
-    if multisig.creator != *creator_account.key() { // you cannot compare a [u8;32] with &[u8;32]
+    // Read from the raw bytes since an older account may be too small to load yet
+    if multisig_account.try_borrow_data()?.get(..32) != Some(creator_account.key().as_ref()) {
         log!("Error: Signer is not the authorized creator of this multisig.");
         return Err(ProgramError::IllegalOwner);
     }
 
+    if multisig_account.data_len() < Multisig::LEN {
+        migrate_multisig(creator_account, multisig_account, accounts)?;
+    }
+
+    let multisig = load_multisig(multisig_account)?;
+
     if data.len() < 16 { //Good way to make sure the user is passing the instruction
         log!("Error: Instruction data is invalid. Expected 16 bytes.");
         return Err(ProgramError::InvalidInstructionData);
@@ -35,6 +58,31 @@ pub fn process_update_multisig_instruction(accounts: &[AccountInfo], data: &[u8]
 
     let new_threshold = u64::from_le_bytes(data[0..8].try_into().unwrap());//getting the new threshold
     let new_proposal_expiry = u64::from_le_bytes(data[8..16].try_into().unwrap());//getting the new expiry
+    let new_time_unit = match data.get(16) {
+        Some(unit) => TimeUnit::try_from(unit)?,
+        None => multisig.time_unit,
+    };
+    // A quiet period or time lock in seconds would otherwise be read as slots or epochs
+    if new_time_unit != multisig.time_unit && data.len() < 50 {
+        log!("Error: Changing the time unit requires the quiet period, time lock and execution window.");
+        return Err(ProgramError::InvalidInstructionData);
+    }
+    let (new_quiet_period, new_quiet_extension, new_max_extensions) = match data.get(17..34) {
+        Some(quiet) => (
+            u64::from_le_bytes(quiet[0..8].try_into().unwrap()),
+            u64::from_le_bytes(quiet[8..16].try_into().unwrap()),
+            quiet[16],
+        ),
+        None => (multisig.quiet_period, multisig.quiet_extension, multisig.max_extensions),
+    };
+    let new_time_lock = match data.get(34..42) {
+        Some(bytes) => u64::from_le_bytes(bytes.try_into().unwrap()),
+        None => multisig.time_lock,
+    };
+    let new_execution_window = match data.get(42..50) {
+        Some(bytes) => u64::from_le_bytes(bytes.try_into().unwrap()),
+        None => multisig.execution_window,
+    };
 
     //Validate and Update
     log!(
@@ -48,16 +96,77 @@ pub fn process_update_multisig_instruction(accounts: &[AccountInfo], data: &[u8]
         new_proposal_expiry
     );
 
+    log!(
+        "Current time unit: {}, New time unit: {}",
+        multisig.time_unit as u8,
+        new_time_unit as u8
+    );
+
+    log!(
+        "Quiet period: {}, Extension: {}, Max extensions: {}",
+        new_quiet_period,
+        new_quiet_extension,
+        new_max_extensions
+    );
+
+    log!(
+        "Current time lock: {}, New time lock: {}",
+        multisig.time_lock,
+        new_time_lock
+    );
+
+    log!(
+        "Current execution window: {}, New execution window: {}",
+        multisig.execution_window,
+        new_execution_window
+    );
+
     // The new threshold must be greater than 0 and cannot exceed the number of members
     if new_threshold == 0 || new_threshold > multisig.member_count as u64 {
         log!("Error: Invalid threshold. It must be between 1 and the member count.");
         return Err(ProgramError::InvalidInstructionData);
     }
 
+    // Pending proposals keep the threshold they were created with
     multisig.threshold = new_threshold;
     multisig.proposal_expiry = new_proposal_expiry;
+    // Existing proposals keep the unit they were created with
+    multisig.time_unit = new_time_unit;
+    multisig.quiet_period = new_quiet_period;
+    multisig.quiet_extension = new_quiet_extension;
+    multisig.max_extensions = new_max_extensions;
+    multisig.time_lock = new_time_lock;
+    multisig.execution_window = new_execution_window;
 
     log!("Multisig successfully updated.");
 
     Ok(())
 }
+
+/// Grows a multisig account created with an older layout to `Multisig::LEN`
+/// Fields are only ever appended, so the existing bytes keep their meaning
+fn migrate_multisig(
+    creator_account: &AccountInfo,
+    multisig_account: &AccountInfo,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    if accounts.get(2).map(|account| account.key()) != Some(&pinocchio_system::ID) {
+        log!("Error: The system program is required to migrate this multisig.");
+        return Err(ProgramError::NotEnoughAccountKeys);
+    }
+
+    log!("Migrating multisig from {} to {} bytes", multisig_account.data_len(), Multisig::LEN);
+
+    let rent = Rent::get()?.minimum_balance(Multisig::LEN);
+    let shortfall = rent.saturating_sub(multisig_account.lamports());
+    if shortfall > 0 {
+        pinocchio_system::instructions::Transfer {
+            from: creator_account,
+            to: multisig_account,
+            lamports: shortfall,
+        }
+        .invoke()?;
+    }
+
+    multisig_account.resize(Multisig::LEN)
+}
@@ -0,0 +1,163 @@
+use pinocchio::sysvars::rent::Rent;
+use pinocchio::sysvars::Sysvar;
+use pinocchio::{
+    account_info::AccountInfo,
+    instruction::{Seed, Signer},
+    program_error::ProgramError,
+    pubkey::{self, Pubkey},
+    ProgramResult,
+};
+use pinocchio_log::log;
+
+use crate::state::{CreateVault, FixedPayload, Multisig, Vault};
+use crate::utils::create_program_address;
+
+/// Runs `f` with the signer seeds of a multisig vault (index 0 is the treasury)
+pub fn with_vault_signer<R>(
+    multisig_key: &Pubkey,
+    index: u8,
+    bump: u8,
+    f: impl FnOnce(&Signer) -> R,
+) -> R {
+    let bump_seed = [bump];
+    if index == 0 {
+        let seeds = [
+            Seed::from(b"treasury"),
+            Seed::from(multisig_key.as_ref()),
+            Seed::from(&bump_seed),
+        ];
+        f(&Signer::from(&seeds))
+    } else {
+        let index_seed = [index];
+        let seeds = [
+            Seed::from(b"vault"),
+            Seed::from(multisig_key.as_ref()),
+            Seed::from(&index_seed),
+            Seed::from(&bump_seed),
+        ];
+        f(&Signer::from(&seeds))
+    }
+}
+
+/// Address of the vault at `index` of the multisig
+pub fn vault_address(multisig_key: &Pubkey, index: u8, bump: u8) -> Result<Pubkey, ProgramError> {
+    let bump_seed = [bump];
+    let index_seed = [index];
+    if index == 0 {
+        create_program_address(&[b"treasury", multisig_key.as_ref(), &bump_seed], &crate::ID)
+    } else {
+        create_program_address(
+            &[b"vault", multisig_key.as_ref(), &index_seed, &bump_seed],
+            &crate::ID,
+        )
+    }
+}
+
+/// Checks that `vault` is the vault at `index` of the multisig
+pub fn verify_vault(multisig_key: &Pubkey, index: u8, bump: u8, vault: &AccountInfo) -> ProgramResult {
+    if &vault_address(multisig_key, index, bump)? != vault.key() {
+        log!("Error: Vault account does not match vault index {}.", index);
+        return Err(ProgramError::InvalidArgument);
+    }
+    Ok(())
+}
+
+/// Loads the threshold and bump of the vault at `index`
+/// `vault_config` is only read for indexed vaults (index 1 and up)
+pub fn load_vault_settings(
+    multisig: &Multisig,
+    multisig_key: &Pubkey,
+    index: u8,
+    vault_config: Option<&AccountInfo>,
+) -> Result<(u64, u8), ProgramError> {
+    if index == 0 {
+        return Ok((multisig.threshold, multisig.treasury_bump));
+    }
+
+    let Some(vault_config) = vault_config else {
+        log!("Error: Vault config account is required for vault {}.", index);
+        return Err(ProgramError::NotEnoughAccountKeys);
+    };
+    if vault_config.owner() != &crate::ID {
+        log!("Error: Vault config must be owned by this program.");
+        return Err(ProgramError::IllegalOwner);
+    }
+
+    let vault = Vault::from_account_info(vault_config)?;
+    let pda_config = create_program_address(
+        &[b"vault_config", multisig_key.as_ref(), &[index], &[vault.config_bump]],
+        &crate::ID,
+    )?;
+    if &pda_config != vault_config.key() || vault.index != index {
+        log!("Error: Vault config does not match vault {}.", index);
+        return Err(ProgramError::InvalidArgument);
+    }
+
+    Ok((vault.threshold, vault.vault_bump))
+}
+
+/// Executes a `CreateVault` proposal, creating the config of the next indexed vault
+/// Accounts expected (after the common execution accounts)
+/// - vault config (`[b"vault_config", multisig, index]`), system program
+pub fn execute_create_vault(
+    payload: &[u8],
+    payer: &AccountInfo,
+    multisig_account: &AccountInfo,
+    multisig: &mut Multisig,
+    remaining: &[AccountInfo],
+) -> ProgramResult {
+    let threshold = CreateVault::from_bytes(payload)?.threshold;
+
+    let [vault_config, _system_program, ..] = remaining else {
+        log!("Error: Not enough accounts provided to create the vault.");
+        return Err(ProgramError::NotEnoughAccountKeys);
+    };
+
+    if threshold == 0 || threshold > multisig.member_count as u64 {
+        log!("Error: Invalid threshold. It must be between 1 and the member count.");
+        return Err(ProgramError::InvalidInstructionData);
+    }
+
+    let index = multisig.vault_count.checked_add(1).ok_or(ProgramError::InvalidArgument)?;
+    let index_seed = [index];
+    let (pda_config, config_bump) = pubkey::find_program_address(
+        &[b"vault_config", multisig_account.key().as_ref(), &index_seed],
+        &crate::ID,
+    );
+    if &pda_config != vault_config.key() {
+        log!("Error: Invalid vault config PDA for vault {}.", index);
+        return Err(ProgramError::InvalidArgument);
+    }
+    let (_, vault_bump) = pubkey::find_program_address(
+        &[b"vault", multisig_account.key().as_ref(), &index_seed],
+        &crate::ID,
+    );
+
+    let bump_seed = [config_bump];
+    let seeds = [
+        Seed::from(b"vault_config"),
+        Seed::from(multisig_account.key().as_ref()),
+        Seed::from(&index_seed),
+        Seed::from(&bump_seed),
+    ];
+
+    pinocchio_system::instructions::CreateAccount {
+        from: payer,
+        to: vault_config,
+        lamports: Rent::get()?.minimum_balance(Vault::LEN),
+        space: Vault::LEN as u64,
+        owner: &crate::ID,
+    }
+    .invoke_signed(&[Signer::from(&seeds)])?;
+
+    let vault = Vault::from_account_info(vault_config)?;
+    vault.multisig = *multisig_account.key();
+    vault.threshold = threshold;
+    vault.index = index;
+    vault.vault_bump = vault_bump;
+    vault.config_bump = config_bump;
+    multisig.vault_count = index;
+
+    log!("Created vault {} with threshold {}", index, threshold);
+    Ok(())
+}
@@ -0,0 +1,170 @@
+use pinocchio::{
+    account_info::AccountInfo,
+    cpi::set_return_data,
+    program_error::ProgramError,
+    pubkey::Pubkey,
+    ProgramResult,
+};
+use pinocchio_log::log;
+
+use crate::instructions::load_proposal;
+use crate::state::ProposalStatus;
+
+/// Result of `VerifyProposal`, returned with `set_return_data`
+/// Integrators usually only need `approved`; the other fields describe the proposal
+#[repr(C, packed)]
+#[derive(Clone, Copy)]
+pub struct ProposalVerification {
+    pub multisig: Pubkey,          // Multisig the proposal belongs to
+    pub payload_hash: [u8; 32],    // SHA-256 of the proposal's payload
+    pub id: u64,                   // ID of the proposal
+    pub executable_after: u64,     // Earliest execution time (in the proposal's time unit)
+    pub status: u8,                // See `ProposalStatus`
+    pub kind: u8,                  // See `ProposalKind`
+    pub vault_index: u8,           // Vault the proposal acts on
+    pub multisig_matches: u8,      // 1 if the proposal belongs to the expected multisig
+    pub payload_hash_matches: u8,  // 1 if the payload hash matches the expected one (or none was given)
+    pub approved: u8,              // 1 if all the above match and the proposal succeeded (or was executed)
+}
+
+impl ProposalVerification {
+    pub const LEN: usize = 32 + 32 + 8 + 8 + 1 + 1 + 1 + 1 + 1 + 1;
+
+    pub fn as_bytes(&self) -> &[u8] {
+        unsafe { core::slice::from_raw_parts(self as *const Self as *const u8, Self::LEN) }
+    }
+}
+
+/// Read-only check that a proposal was approved, meant to be called through CPI
+/// Fails only if the account is not a proposal of this program (its PDA is re-derived, so
+/// other accounts of the program cannot pass for one); otherwise returns a
+/// `ProposalVerification` as return data
+/// Instruction data (`data`) expected
+/// - 32 bytes: The multisig the proposal must belong to
+/// - 32 bytes (optional): The SHA-256 hash the proposal's payload must have
+///
+/// Accounts expected
+/// - proposal
+pub fn process_verify_proposal_instruction(accounts: &[AccountInfo], data: &[u8]) -> ProgramResult {
+    let [proposal_account, ..] = accounts else {
+        log!("Error: Not enough accounts provided. Expected the proposal.");
+        return Err(ProgramError::NotEnoughAccountKeys);
+    };
+
+    let verification = verify_proposal(proposal_account, data)?;
+    set_return_data(verification.as_bytes());
+    Ok(())
+}
+
+/// Compares a proposal against the multisig and payload hash expected in `data`
+fn verify_proposal(proposal_account: &AccountInfo, data: &[u8]) -> Result<ProposalVerification, ProgramError> {
+    let Some(expected_multisig) = data.get(0..32) else {
+        log!("Error: Instruction data is invalid. Expected the multisig.");
+        return Err(ProgramError::InvalidInstructionData);
+    };
+    let expected_hash = data.get(32..64);
+
+    let proposal = load_proposal(proposal_account)?;
+
+    let multisig_matches = proposal.multisig.as_ref() == expected_multisig;
+    let payload_hash_matches = !matches!(expected_hash, Some(hash) if hash != proposal.payload_hash.as_slice());
+    let succeeded = matches!(proposal.status, ProposalStatus::Succeeded | ProposalStatus::Executed);
+
+    Ok(ProposalVerification {
+        multisig: proposal.multisig,
+        payload_hash: proposal.payload_hash,
+        id: proposal.id,
+        executable_after: proposal.executable_after,
+        status: proposal.status as u8,
+        kind: proposal.kind as u8,
+        vault_index: proposal.vault_index,
+        multisig_matches: multisig_matches as u8,
+        payload_hash_matches: payload_hash_matches as u8,
+        approved: (multisig_matches && payload_hash_matches && succeeded) as u8,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::Proposal;
+    use crate::test_utils::{proposal_account, TestAccount};
+
+    const MULTISIG: Pubkey = [0x11; 32];
+    const HASH: [u8; 32] = [0xAB; 32];
+
+    fn succeeded_proposal() -> TestAccount {
+        let mut account = proposal_account(&MULTISIG, 3, ProposalStatus::Succeeded);
+        account.state::<Proposal>().payload_hash = HASH;
+        account
+    }
+
+    fn expecting(multisig: &Pubkey, hash: Option<&[u8; 32]>) -> Vec<u8> {
+        [multisig.as_ref(), hash.map_or(&[][..], |hash| hash.as_ref())].concat()
+    }
+
+    #[test]
+    fn approves_a_succeeded_proposal_of_the_expected_multisig() {
+        let mut account = succeeded_proposal();
+
+        let verification = verify_proposal(&account.info(), &expecting(&MULTISIG, Some(&HASH))).unwrap();
+        assert_eq!(verification.approved, 1);
+        assert_eq!({ verification.id }, 3);
+        assert_eq!(verification.multisig, MULTISIG);
+
+        // Without an expected hash, any payload is accepted
+        let verification = verify_proposal(&account.info(), &expecting(&MULTISIG, None)).unwrap();
+        assert_eq!(verification.approved, 1);
+    }
+
+    #[test]
+    fn does_not_approve_other_multisigs_payloads_or_pending_proposals() {
+        let mut account = succeeded_proposal();
+        let verification = verify_proposal(&account.info(), &expecting(&[0x22; 32], Some(&HASH))).unwrap();
+        assert_eq!((verification.multisig_matches, verification.approved), (0, 0));
+
+        let verification = verify_proposal(&account.info(), &expecting(&MULTISIG, Some(&[0xCD; 32]))).unwrap();
+        assert_eq!((verification.payload_hash_matches, verification.approved), (0, 0));
+
+        let mut account = proposal_account(&MULTISIG, 3, ProposalStatus::Active);
+        account.state::<Proposal>().payload_hash = HASH;
+        let verification = verify_proposal(&account.info(), &expecting(&MULTISIG, Some(&HASH))).unwrap();
+        assert_eq!((verification.status, verification.approved), (ProposalStatus::Active as u8, 0));
+    }
+
+    #[test]
+    fn rejects_a_forged_proposal() {
+        // The same bytes as an approved proposal, in a program account that is not its PDA
+        // (e.g. a transaction buffer)
+        let mut forged = TestAccount::new([0x33; 32], crate::ID, Proposal::LEN);
+        let proposal = forged.state::<Proposal>();
+        proposal.multisig = MULTISIG;
+        proposal.id = 3;
+        proposal.bump = 255;
+        proposal.status = ProposalStatus::Succeeded;
+        proposal.payload_hash = HASH;
+        assert_eq!(
+            verify_proposal(&forged.info(), &expecting(&MULTISIG, Some(&HASH))).err(),
+            Some(ProgramError::InvalidArgument)
+        );
+
+        // A real proposal PDA claiming to be another proposal of the multisig
+        let mut account = succeeded_proposal();
+        account.state::<Proposal>().id = 4;
+        assert_eq!(
+            verify_proposal(&account.info(), &expecting(&MULTISIG, Some(&HASH))).err(),
+            Some(ProgramError::InvalidArgument)
+        );
+    }
+
+    #[test]
+    fn rejects_accounts_of_other_programs() {
+        // At the proposal's address, but owned by another program
+        let key = *succeeded_proposal().info().key();
+        let mut foreign = TestAccount::new(key, [0x44; 32], Proposal::LEN);
+        assert_eq!(
+            verify_proposal(&foreign.info(), &expecting(&MULTISIG, Some(&HASH))).err(),
+            Some(ProgramError::IllegalOwner)
+        );
+    }
+}
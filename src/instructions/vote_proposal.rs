@@ -9,7 +9,8 @@ use pinocchio::sysvars::rent::Rent;
 use pinocchio::sysvars::clock::Clock;
 use pinocchio_log::log;
 
-use crate::state::{Proposal, ProposalStatus, VoteState};
+use crate::instructions::{load_multisig, load_proposal};
+use crate::state::{Proposal, ProposalKind, ProposalStatus, VoteState};
 
 /// Processes a member's vote on an active proposal
 /// This instruction validates the voter's eligibility, checks if the proposal is
@@ -17,22 +18,34 @@ use crate::state::{Proposal, ProposalStatus, VoteState};
 /// account for the voter to track their participation
 /// Instruction data (`data`) expected
 /// - 1 byte: The vote, where `1` = Yes and `0` = No.
+///
+/// If a vote cast within the proposal's quiet period flips whether it would pass,
+/// the deadline is extended so the other members have time to react
+/// A member that is itself a multisig (a child multisig) votes without signing: the voter
+/// account is the child multisig, followed (after the system program) by a succeeded
+/// `ParentVote` proposal of the child deciding this vote, and a payer (signer) for the
+/// child's `VoteState`. The child proposal is marked executed so it cannot be reused
 pub fn process_vote_instruction(
     accounts: &[AccountInfo],
     data: &[u8],
 ) -> ProgramResult {
-    let [voter_account, proposal_account, vote_state_account, system_program, ..] = accounts else {
+    let [voter_account, proposal_account, vote_state_account, system_program, remaining @ ..] = accounts else {
         log!("Error: Not enough accounts provided. Expected 4.");
         return Err(ProgramError::NotEnoughAccountKeys);
     };
 
-    if !voter_account.is_signer() {
+    let clock = Clock::get()?;
+    let payer = if voter_account.is_signer() {
+        voter_account
+    } else if voter_account.owner() == &crate::ID {
+        verify_child_vote(voter_account, proposal_account, data, remaining, &clock)?
+    } else {
         log!("Error: The voter account must be a signer.");
         return Err(ProgramError::MissingRequiredSignature);
-    }
+    };
 
-    let proposal = Proposal::from_account_info(proposal_account)?;
-    let clock = Clock::get()?;
+    let proposal = load_proposal(proposal_account)?;
+    let now = proposal.time_unit.current(&clock);
 
     // Ensure the proposal is currently active for voting.
     if proposal.status != ProposalStatus::Active {
@@ -40,8 +53,14 @@ pub fn process_vote_instruction(
         return Err(ProgramError::InvalidAccountData);
     }
 
+    // Ensure voting has opened (proposals may be published ahead of their voting window)
+    if now < proposal.voting_starts_at {
+        log!("Error: Voting has not started for this proposal yet.");
+        return Err(ProgramError::InvalidAccountData);
+    }
+
     // Ensure the voting period has not expired
-    if clock.unix_timestamp as u64 > proposal.expiration_time {
+    if now > proposal.expiration_time {
         log!("Error: Voting has expired for this proposal.");
         // Optionally, update the status to Failed.
         proposal.status = ProposalStatus::Failed;
@@ -72,7 +91,30 @@ pub fn process_vote_instruction(
             }
 
             log!("Voter found at index {}. Recording vote: {}.", index, vote);
+            let passing_before = yes_votes(proposal) >= proposal.threshold;
             proposal.votes[index] = vote;
+            let passing_after = yes_votes(proposal) >= proposal.threshold;
+
+            // Anti-sniping: a late vote that flips the outcome pushes the deadline back
+            let in_quiet_period = proposal.expiration_time - now < proposal.quiet_period;
+            if passing_before != passing_after
+                && in_quiet_period
+                && proposal.extensions_used < proposal.max_extensions
+            {
+                let Some(expiration_time) = proposal.expiration_time.checked_add(proposal.quiet_extension)
+                else {
+                    log!("Error: Voting cannot be extended that late.");
+                    return Err(ProgramError::ArithmeticOverflow);
+                };
+                proposal.expiration_time = expiration_time;
+                proposal.extensions_used += 1;
+                log!(
+                    "Late outcome flip. Voting extended to {} ({} of {} extensions used).",
+                    proposal.expiration_time,
+                    proposal.extensions_used,
+                    proposal.max_extensions
+                );
+            }
         }
         None => {
             log!("Error: Signer is not in the list of eligible voters for this proposal.");
@@ -92,7 +134,7 @@ pub fn process_vote_instruction(
         log!("First-time voter detected. Creating VoteState account...");
 
         pinocchio_system::instructions::CreateAccount {
-            from: voter_account,
+            from: payer,
             to: vote_state_account,
             lamports: Rent::get()?.minimum_balance(VoteState::LEN),
             space: VoteState::LEN as u64,
@@ -116,4 +158,193 @@ pub fn process_vote_instruction(
 
     log!(" Vote successfully processed.");
     Ok(())
-}
\ No newline at end of file
+}
+
+/// Checks that a child multisig decided this vote through one of its own proposals, then
+/// consumes that proposal. Returns the payer of the child's `VoteState`
+/// Accounts expected in `remaining`
+/// - child proposal (a succeeded `ParentVote` proposal of the child multisig), payer (signer)
+fn verify_child_vote<'a>(
+    child_multisig: &AccountInfo,
+    proposal_account: &AccountInfo,
+    data: &[u8],
+    remaining: &'a [AccountInfo],
+    clock: &Clock,
+) -> Result<&'a AccountInfo, ProgramError> {
+    let [child_proposal_account, payer, ..] = remaining else {
+        log!("Error: A child multisig votes with its proposal and a payer.");
+        return Err(ProgramError::NotEnoughAccountKeys);
+    };
+
+    if !payer.is_signer() {
+        log!("Error: The payer must sign the transaction.");
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+
+    // Both are re-derived from their seeds: any other account of the program could hold
+    // the bytes of a multisig or of a succeeded proposal
+    load_multisig(child_multisig)?;
+    if child_proposal_account.key() == proposal_account.key() {
+        log!("Error: Invalid child proposal account.");
+        return Err(ProgramError::InvalidArgument);
+    }
+    let child_proposal = load_proposal(child_proposal_account)?;
+
+    if child_proposal.multisig != *child_multisig.key() || child_proposal.kind != ProposalKind::ParentVote {
+        log!("Error: Child proposal is not a parent vote of the voting multisig.");
+        return Err(ProgramError::InvalidArgument);
+    }
+
+    if child_proposal.status != ProposalStatus::Succeeded {
+        log!("Error: Only succeeded child proposals can cast a vote.");
+        return Err(ProgramError::InvalidAccountData);
+    }
+
+    // The child's time lock and execution window apply as if the vote were executed
+    let now = child_proposal.time_unit.current(clock);
+    if now < child_proposal.executable_after {
+        log!("Error: Child proposal is time locked until {}.", child_proposal.executable_after);
+        return Err(ProgramError::InvalidArgument);
+    }
+    if child_proposal.execution_deadline != 0 && now > child_proposal.execution_deadline {
+        log!("Error: The execution window for the child proposal has lapsed.");
+        return Err(ProgramError::InvalidArgument);
+    }
+
+    // The child approved the payload by hash: the parent proposal and the vote being cast
+    let vote = *data.first().ok_or(ProgramError::InvalidInstructionData)?;
+    if !child_proposal.decides_parent_vote(proposal_account.key(), vote) {
+        log!("Error: Child proposal did not decide this vote.");
+        return Err(ProgramError::InvalidArgument);
+    }
+
+    child_proposal.status = ProposalStatus::Executed;
+    Ok(payer)
+}
+
+fn yes_votes(proposal: &Proposal) -> u64 {
+    proposal.votes.iter().filter(|&&v| v == 1).count() as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::Multisig;
+    use crate::test_utils::{multisig_account, proposal_account, TestAccount};
+    use crate::utils::sha256;
+
+    const PARENT_PROPOSAL: Pubkey = [0x11; 32];
+
+    fn clock(unix_timestamp: i64) -> Clock {
+        Clock { slot: 0, epoch_start_timestamp: 0, epoch: 0, leader_schedule_epoch: 0, unix_timestamp }
+    }
+
+    /// The child multisig, and its `ParentVote` proposal approving a yes vote on the parent
+    /// proposal, executable between 100 and 200
+    fn child() -> (TestAccount, TestAccount) {
+        let mut child_multisig = multisig_account(&[0x22; 32], &[[0x23; 32], [0x24; 32]], 2);
+        let mut child_proposal = proposal_account(child_multisig.info().key(), 0, ProposalStatus::Succeeded);
+        let proposal = child_proposal.state::<Proposal>();
+        proposal.kind = ProposalKind::ParentVote;
+        proposal.payload_hash = sha256(&[PARENT_PROPOSAL.as_ref(), &[1]].concat());
+        proposal.executable_after = 100;
+        proposal.execution_deadline = 200;
+        (child_multisig, child_proposal)
+    }
+
+    fn vote(
+        child_multisig: &mut TestAccount,
+        child_proposal: &mut TestAccount,
+        vote: u8,
+        now: i64,
+    ) -> Result<(), ProgramError> {
+        let mut parent_proposal = TestAccount::new(PARENT_PROPOSAL, crate::ID, Proposal::LEN);
+        let mut payer = TestAccount::new([0x99; 32], pinocchio_system::ID, 0).signer();
+        let remaining = [child_proposal.info(), payer.info()];
+        verify_child_vote(&child_multisig.info(), &parent_proposal.info(), &[vote], &remaining, &clock(now))
+            .map(|payer| assert_eq!(payer.key(), &[0x99; 32]))
+    }
+
+    #[test]
+    fn casts_the_vote_the_child_approved_once() {
+        let (mut child_multisig, mut child_proposal) = child();
+        assert_eq!(vote(&mut child_multisig, &mut child_proposal, 0, 150), Err(ProgramError::InvalidArgument));
+
+        assert_eq!(vote(&mut child_multisig, &mut child_proposal, 1, 150), Ok(()));
+        assert!(child_proposal.state::<Proposal>().status == ProposalStatus::Executed);
+
+        // The child proposal is consumed
+        assert_eq!(
+            vote(&mut child_multisig, &mut child_proposal, 1, 150),
+            Err(ProgramError::InvalidAccountData)
+        );
+    }
+
+    #[test]
+    fn rejects_child_proposals_of_other_programs() {
+        let (mut child_multisig, mut child_proposal) = child();
+        let mut foreign = TestAccount::new(*child_proposal.info().key(), [0x44; 32], Proposal::LEN);
+        assert_eq!(vote(&mut child_multisig, &mut foreign, 1, 150), Err(ProgramError::IllegalOwner));
+    }
+
+    #[test]
+    fn rejects_a_forged_child_proposal() {
+        let (mut child_multisig, mut child_proposal) = child();
+
+        // The bytes of the approved child proposal, in a program account that is not its PDA
+        let mut forged = TestAccount::new([0x55; 32], crate::ID, Proposal::LEN);
+        let approved = child_proposal.state::<Proposal>();
+        let proposal = forged.state::<Proposal>();
+        proposal.multisig = approved.multisig;
+        proposal.bump = approved.bump;
+        proposal.kind = approved.kind;
+        proposal.status = approved.status;
+        proposal.payload_hash = approved.payload_hash;
+        proposal.executable_after = approved.executable_after;
+        proposal.execution_deadline = approved.execution_deadline;
+        assert_eq!(vote(&mut child_multisig, &mut forged, 1, 150), Err(ProgramError::InvalidArgument));
+    }
+
+    #[test]
+    fn rejects_proposals_of_another_multisig() {
+        let (_, mut child_proposal) = child();
+        let mut other_multisig = multisig_account(&[0x66; 32], &[[0x23; 32]], 1);
+        assert_eq!(
+            vote(&mut other_multisig, &mut child_proposal, 1, 150),
+            Err(ProgramError::InvalidArgument)
+        );
+
+        // A voter holding the child multisig's bytes without being its PDA
+        let (mut child_multisig, mut child_proposal) = child();
+        let mut forged_multisig = TestAccount::new([0x77; 32], crate::ID, Multisig::LEN);
+        forged_multisig.state::<Multisig>().creator = child_multisig.state::<Multisig>().creator;
+        forged_multisig.state::<Multisig>().config_bump = child_multisig.state::<Multisig>().config_bump;
+        child_proposal.state::<Proposal>().multisig = [0x77; 32];
+        assert_eq!(
+            vote(&mut forged_multisig, &mut child_proposal, 1, 150),
+            Err(ProgramError::InvalidArgument)
+        );
+    }
+
+    #[test]
+    fn only_succeeded_parent_votes_count() {
+        let (mut child_multisig, mut child_proposal) = child();
+        child_proposal.state::<Proposal>().status = ProposalStatus::Active;
+        assert_eq!(
+            vote(&mut child_multisig, &mut child_proposal, 1, 150),
+            Err(ProgramError::InvalidAccountData)
+        );
+
+        let (mut child_multisig, mut child_proposal) = child();
+        child_proposal.state::<Proposal>().kind = ProposalKind::Standard;
+        assert_eq!(vote(&mut child_multisig, &mut child_proposal, 1, 150), Err(ProgramError::InvalidArgument));
+    }
+
+    #[test]
+    fn applies_the_child_time_lock_and_execution_window() {
+        let (mut child_multisig, mut child_proposal) = child();
+        assert_eq!(vote(&mut child_multisig, &mut child_proposal, 1, 99), Err(ProgramError::InvalidArgument));
+        assert_eq!(vote(&mut child_multisig, &mut child_proposal, 1, 201), Err(ProgramError::InvalidArgument));
+        assert_eq!(vote(&mut child_multisig, &mut child_proposal, 1, 200), Ok(()));
+    }
+}
@@ -13,6 +13,9 @@ use pinocchio::{
 
 mod state;
 mod instructions;
+mod utils;
+#[cfg(test)]
+mod test_utils;
 
 use instructions::*;
 
@@ -36,6 +39,17 @@ pub fn process_instruction(
         MultisigInstructions::UpdateMultisig => instructions::process_update_multisig_instruction(accounts, data)?,
         MultisigInstructions::Vote => instructions::process_vote_instruction(accounts, data)?,
         MultisigInstructions::CloseProposal => instructions::process_close_proposal_instruction(accounts, data)?,
+        MultisigInstructions::ExecuteProposal => instructions::process_execute_proposal_instruction(accounts, data)?,
+        MultisigInstructions::TransactionBuffer => instructions::process_transaction_buffer_instruction(accounts, data)?,
+        MultisigInstructions::InitializeProposalFromBuffer => instructions::process_initialize_proposal_from_buffer_instruction(accounts, data)?,
+        MultisigInstructions::UseSpendingLimit => instructions::process_use_spending_limit_instruction(accounts, data)?,
+        MultisigInstructions::CrankPaymentSchedule => instructions::process_crank_payment_schedule_instruction(accounts, data)?,
+        MultisigInstructions::ClaimStream => instructions::process_claim_stream_instruction(accounts, data)?,
+        MultisigInstructions::ApproveMilestone => instructions::process_approve_milestone_instruction(accounts, data)?,
+        MultisigInstructions::SpendBudget => instructions::process_spend_budget_instruction(accounts, data)?,
+        MultisigInstructions::InitializeLedger => instructions::process_initialize_ledger_instruction(accounts, data)?,
+        MultisigInstructions::VerifyProposal => instructions::process_verify_proposal_instruction(accounts, data)?,
+        MultisigInstructions::ExecuteModule => instructions::process_execute_module_instruction(accounts, data)?,
     }
 
     Ok(())
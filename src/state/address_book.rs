@@ -0,0 +1,54 @@
+use pinocchio::{account_info::AccountInfo, program_error::ProgramError, pubkey::Pubkey};
+
+/// Allowlist of approved transfer recipients, managed by proposals
+/// Transfer proposals to a recipient outside the book (or above its cap) need
+/// `unlisted_threshold` approvals instead of the vault threshold, if that is higher
+/// PDA: [b"address_book", multisig]
+#[repr(C)]
+pub struct AddressBook {
+    pub multisig: Pubkey,                   // Multisig the book belongs to
+    pub entries: [AddressBookEntry; 32],    // Approved recipients
+    pub unlisted_threshold: u64,            // Approvals required for transfers outside the book
+    pub entry_count: u8,                    // Number of entries in use
+    pub bump: u8,                           // PDA bump for this account
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct AddressBookEntry {
+    pub recipient: Pubkey, // Approved recipient wallet
+    pub cap: u64,          // Largest amount per transfer (0 = no cap)
+}
+
+impl AddressBook {
+    pub const MAX_ENTRIES: usize = 32;
+    pub const LEN: usize = core::mem::size_of::<Self>();
+
+    pub fn from_account_info_unchecked(account_info: &AccountInfo) -> &mut Self {
+        unsafe { &mut *(account_info.borrow_mut_data_unchecked().as_ptr() as *mut Self) }
+    }
+
+    pub fn from_account_info(account_info: &AccountInfo) -> Result<&mut Self, ProgramError> {
+        if account_info.data_len() < Self::LEN {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        Ok(Self::from_account_info_unchecked(account_info))
+    }
+
+    pub fn position(&self, recipient: &Pubkey) -> Option<usize> {
+        self.entries[..self.entry_count as usize]
+            .iter()
+            .position(|entry| entry.recipient == *recipient)
+    }
+
+    /// Whether a transfer of `amount` to `recipient` is covered by the book
+    pub fn allows(&self, recipient: &Pubkey, amount: u64) -> bool {
+        match self.position(recipient) {
+            Some(index) => {
+                let cap = self.entries[index].cap;
+                cap == 0 || amount <= cap
+            }
+            None => false,
+        }
+    }
+}
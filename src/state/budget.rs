@@ -0,0 +1,83 @@
+use pinocchio::{account_info::AccountInfo, program_error::ProgramError, pubkey::Pubkey};
+
+/// An amount of a vault's funds that a set of owners can spend until the budget expires
+/// Funds stay in the vault; each spend needs `threshold` owner approvals instead of a proposal
+/// A budget with a period allows the amount again in each period (e.g. 10 SOL a month)
+/// Created by a `CreateBudget` proposal
+/// PDA: [b"budget", proposal]
+#[repr(C)]
+pub struct Budget {
+    pub proposal: Pubkey,     // Proposal that created the budget (seed of this PDA)
+    pub multisig: Pubkey,     // Multisig the budget belongs to
+    pub mint: Pubkey,         // Token mint, or the default pubkey for SOL
+    pub name: [u8; 32],       // Label of the budget, zero padded
+    pub owners: [Pubkey; 10], // Members who spend the budget
+    pub amount: u64,          // Total that can be spent
+    pub spent: u64,           // Total spent so far in the current period
+    pub expires_at: u64,      // Unix timestamp after which nothing can be spent
+    pub period: u64,          // Seconds after which `spent` resets (0 = never)
+    pub period_start: u64,    // Unix timestamp the current period started at
+    pub threshold: u64,       // Owner approvals needed for each spend
+    pub spend_count: u64,     // Number of spend requests created (seed of the next one)
+    pub proposal_id: u64,     // Proposal that created the budget
+    pub owner_count: u8,      // Number of entries in `owners`
+    pub vault_index: u8,      // Vault the funds are taken from (0 = treasury)
+    pub vault_bump: u8,       // Bump of that vault PDA
+    pub bump: u8,             // PDA bump for this account
+}
+
+impl Budget {
+    pub const LEN: usize = core::mem::size_of::<Self>();
+
+    pub fn from_account_info_unchecked(account_info: &AccountInfo) -> &mut Self {
+        unsafe { &mut *(account_info.borrow_mut_data_unchecked().as_ptr() as *mut Self) }
+    }
+
+    pub fn from_account_info(account_info: &AccountInfo) -> Result<&mut Self, ProgramError> {
+        if account_info.data_len() < Self::LEN {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        Ok(Self::from_account_info_unchecked(account_info))
+    }
+
+    pub fn remaining(&self) -> u64 {
+        self.amount - self.spent
+    }
+
+    /// Moves on to the period containing `now`, clearing what was spent in earlier ones
+    pub fn roll_period(&mut self, now: u64) {
+        if self.period == 0 || now < self.period_start.saturating_add(self.period) {
+            return;
+        }
+        self.period_start += (now - self.period_start) / self.period * self.period;
+        self.spent = 0;
+    }
+}
+
+/// A payment out of a budget, paid once enough owners approve it
+/// PDA: [b"budget_spend", budget, index]
+#[repr(C)]
+pub struct BudgetSpend {
+    pub budget: Pubkey,    // Budget the spend draws from
+    pub recipient: Pubkey, // Recipient wallet (owner of the destination token account for tokens)
+    pub amount: u64,       // Lamports, or token base units
+    pub approvals: u64,    // Bitmask of the budget `owners` that approved it
+    pub index: u64,        // Position among the budget's spend requests
+    pub executed: u8,      // 1 once paid
+    pub bump: u8,          // PDA bump for this account
+}
+
+impl BudgetSpend {
+    pub const LEN: usize = core::mem::size_of::<Self>();
+
+    pub fn from_account_info_unchecked(account_info: &AccountInfo) -> &mut Self {
+        unsafe { &mut *(account_info.borrow_mut_data_unchecked().as_ptr() as *mut Self) }
+    }
+
+    pub fn from_account_info(account_info: &AccountInfo) -> Result<&mut Self, ProgramError> {
+        if account_info.data_len() < Self::LEN {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        Ok(Self::from_account_info_unchecked(account_info))
+    }
+}
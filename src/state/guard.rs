@@ -0,0 +1,36 @@
+use pinocchio::{account_info::AccountInfo, program_error::ProgramError, pubkey::Pubkey};
+
+/// Guard program called before and after anything moves funds out of a multisig's vaults
+/// (proposal execution, spending limits, payment schedules, budgets, modules). Streams and
+/// milestone escrows are funded by a guarded proposal, so their payouts are not guarded again
+/// Set by a `SetGuard` proposal
+/// PDA: [b"guard", multisig]
+#[repr(C)]
+pub struct Guard {
+    pub multisig: Pubkey, // Multisig the guard belongs to
+    pub program: Pubkey,  // Guard program (default pubkey = no guard)
+    pub bump: u8,         // PDA bump for this account
+}
+
+impl Guard {
+    pub const LEN: usize = 32 + 32 + 1;
+
+    pub fn from_account_info_unchecked(account_info: &AccountInfo) -> &mut Self {
+        unsafe { &mut *(account_info.borrow_mut_data_unchecked().as_ptr() as *mut Self) }
+    }
+
+    pub fn from_account_info(account_info: &AccountInfo) -> Result<&mut Self, ProgramError> {
+        if account_info.data_len() < Self::LEN {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        Ok(Self::from_account_info_unchecked(account_info))
+    }
+}
+
+/// Point of execution a guard program is called at, sent as the first byte of its instruction data
+#[repr(u8)]
+#[derive(Clone, Copy, PartialEq)]
+pub enum GuardHook {
+    PreExecution = 0,  // Before the funds move
+    PostExecution = 1, // After the funds moved, with the resulting balances
+}
@@ -0,0 +1,108 @@
+use pinocchio::{account_info::AccountInfo, program_error::ProgramError, pubkey::Pubkey};
+
+/// Record of the funds flowing in and out of a multisig's vaults, kept as a ring buffer
+/// Once it exists, every instruction moving vault funds appends to it
+/// PDA: [b"ledger", multisig]
+#[repr(C)]
+pub struct Ledger {
+    pub multisig: Pubkey,           // Multisig the ledger belongs to
+    pub entry_count: u64,           // Entries appended since creation
+    pub entries: [LedgerEntry; 64], // Most recent entries, the next one goes at `entry_count % MAX_ENTRIES`
+    pub bump: u8,                   // PDA bump for this account
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct LedgerEntry {
+    pub asset: Pubkey,              // Token mint, or the default pubkey for SOL
+    pub counterparty: Pubkey,       // Account funds went to or came from (default pubkey if several, e.g. batches)
+    pub amount: u64,                // Lamports, or token base units
+    pub proposal_id: u64,           // Proposal that authorized the flow (or created the limit, schedule, or budget; 0 for modules)
+    pub timestamp: u64,             // Unix timestamp of the flow
+    pub vault_index: u8,            // Vault the funds moved through (0 = treasury)
+    pub direction: LedgerDirection, // Whether the funds left or entered the vault
+    pub source: LedgerSource,       // Instruction that moved the funds
+    pub reserved: [u8; 5],          // Keeps entries 8-byte aligned
+}
+
+impl Ledger {
+    pub const MAX_ENTRIES: usize = 64;
+    pub const LEN: usize = core::mem::size_of::<Self>();
+
+    pub fn from_account_info_unchecked(account_info: &AccountInfo) -> &mut Self {
+        unsafe { &mut *(account_info.borrow_mut_data_unchecked().as_ptr() as *mut Self) }
+    }
+
+    pub fn from_account_info(account_info: &AccountInfo) -> Result<&mut Self, ProgramError> {
+        if account_info.data_len() < Self::LEN {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        Ok(Self::from_account_info_unchecked(account_info))
+    }
+
+    /// Appends an entry, overwriting the oldest one once the buffer is full
+    pub fn append(&mut self, entry: LedgerEntry) {
+        self.entries[(self.entry_count % Self::MAX_ENTRIES as u64) as usize] = entry;
+        self.entry_count += 1;
+    }
+}
+
+#[repr(u8)]
+#[derive(Clone, Copy, PartialEq)]
+pub enum LedgerDirection {
+    Outflow = 0,
+    Inflow = 1,
+}
+
+#[repr(u8)]
+#[derive(Clone, Copy, PartialEq)]
+pub enum LedgerSource {
+    Proposal = 0,
+    SpendingLimit = 1,
+    PaymentSchedule = 2,
+    Budget = 3,
+    Module = 4,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(amount: u64) -> LedgerEntry {
+        LedgerEntry {
+            asset: Pubkey::default(),
+            counterparty: Pubkey::default(),
+            amount,
+            proposal_id: 0,
+            timestamp: 0,
+            vault_index: 0,
+            direction: LedgerDirection::Outflow,
+            source: LedgerSource::Proposal,
+            reserved: [0; 5],
+        }
+    }
+
+    #[test]
+    fn a_full_ledger_overwrites_its_oldest_entries() {
+        let mut ledger = Ledger {
+            multisig: Pubkey::default(),
+            entry_count: 0,
+            entries: [entry(0); Ledger::MAX_ENTRIES],
+            bump: 0,
+        };
+
+        // Amounts 1 to 64 fill the buffer, 65 to 70 replace 1 to 6
+        for amount in 1..=70 {
+            ledger.append(entry(amount));
+        }
+
+        assert_eq!(ledger.entry_count, 70);
+        let amounts: Vec<u64> = ledger.entries.iter().map(|entry| entry.amount).collect();
+        assert_eq!(amounts[..6], [65, 66, 67, 68, 69, 70]);
+        assert_eq!(amounts[6..], (7..=64).collect::<Vec<u64>>()[..]);
+
+        // The oldest entry kept is the next one to be overwritten
+        let oldest = (ledger.entry_count % Ledger::MAX_ENTRIES as u64) as usize;
+        assert_eq!(ledger.entries[oldest].amount, 7);
+    }
+}
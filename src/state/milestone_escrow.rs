@@ -0,0 +1,57 @@
+use pinocchio::{account_info::AccountInfo, program_error::ProgramError, pubkey::Pubkey};
+
+/// Payment to a contractor escrowed from a vault and released milestone by milestone
+/// SOL is held by this account itself, tokens by `token_escrow` (a token account owned by it)
+/// Each milestone is released once enough of the members snapshotted at creation approve it
+/// Created by a `CreateMilestoneEscrow` proposal
+/// PDA: [b"milestone_escrow", proposal]
+#[repr(C)]
+pub struct MilestoneEscrow {
+    pub proposal: Pubkey,            // Proposal that created the escrow (seed of this PDA)
+    pub multisig: Pubkey,            // Multisig the escrow belongs to
+    pub mint: Pubkey,                // Token mint, or the default pubkey for SOL
+    pub contractor: Pubkey,          // Paid as milestones are released
+    pub token_escrow: Pubkey,        // Token account holding the escrowed tokens (default pubkey for SOL)
+    pub members: [Pubkey; 10],       // Members allowed to approve milestones
+    pub milestones: [Milestone; 8],  // Milestones in order
+    pub threshold: u64,              // Approvals needed to release a milestone
+    pub deadline: u64,               // Unix timestamp after which unreleased funds can be clawed back
+    pub member_count: u8,            // Number of entries in `members`
+    pub milestone_count: u8,         // Number of entries in `milestones`
+    pub cancelled: u8,               // 1 once unreleased funds have been clawed back
+    pub vault_index: u8,             // Vault that funded the escrow (0 = treasury)
+    pub vault_bump: u8,              // Bump of that vault PDA
+    pub bump: u8,                    // PDA bump for this account
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+pub struct Milestone {
+    pub amount: u64,      // Paid to the contractor on release
+    pub approvals: u64,   // Bitmask of the `members` that approved it
+    pub released_at: u64, // Unix timestamp of the release (0 = not released)
+}
+
+impl MilestoneEscrow {
+    pub const LEN: usize = core::mem::size_of::<Self>();
+
+    pub fn from_account_info_unchecked(account_info: &AccountInfo) -> &mut Self {
+        unsafe { &mut *(account_info.borrow_mut_data_unchecked().as_ptr() as *mut Self) }
+    }
+
+    pub fn from_account_info(account_info: &AccountInfo) -> Result<&mut Self, ProgramError> {
+        if account_info.data_len() < Self::LEN {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        Ok(Self::from_account_info_unchecked(account_info))
+    }
+
+    /// Total of the milestones not released yet
+    pub fn unreleased(&self) -> u64 {
+        self.milestones[..self.milestone_count as usize]
+            .iter()
+            .filter(|milestone| milestone.released_at == 0)
+            .map(|milestone| milestone.amount)
+            .sum()
+    }
+}
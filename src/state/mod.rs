@@ -1,7 +1,35 @@
+pub mod address_book;
+pub mod budget;
+pub mod guard;
+pub mod ledger;
+pub mod milestone_escrow;
 pub mod multisig;
+pub mod payload;
+pub mod payment_schedule;
+pub mod program_allowlist;
 pub mod proposal;
+pub mod rate_limit;
+pub mod signed_message;
+pub mod spending_limit;
+pub mod stream;
+pub mod transaction_buffer;
+pub mod vault;
 pub mod vote;
 
+pub use address_book::*;
+pub use budget::*;
+pub use guard::*;
+pub use ledger::*;
+pub use milestone_escrow::*;
 pub use multisig::*;
+pub use payload::*;
+pub use payment_schedule::*;
+pub use program_allowlist::*;
 pub use proposal::*;
-pub use vote::*;
\ No newline at end of file
+pub use rate_limit::*;
+pub use signed_message::*;
+pub use spending_limit::*;
+pub use stream::*;
+pub use transaction_buffer::*;
+pub use vault::*;
+pub use vote::*;
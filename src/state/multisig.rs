@@ -1,6 +1,8 @@
 use pinocchio::{
     account_info::AccountInfo,
+    program_error::ProgramError,
     pubkey::Pubkey,
+    sysvars::clock::Clock,
 };
 
 #[repr(C)]
@@ -14,10 +16,21 @@ pub struct Multisig {
     pub treasury_wallet: Pubkey,    // PDA for the multisig treasury
     pub config_bump: u8,            // Bump for this multisig config PDA
     pub treasury_bump: u8,          // Bump for the treasury PDA
+    pub time_unit: TimeUnit,        // Unit used for proposal voting windows
+    pub quiet_period: u64,          // Final window of a vote in which an outcome flip extends it (0 = disabled)
+    pub quiet_extension: u64,       // How far the deadline is pushed back on a late flip
+    pub max_extensions: u8,         // Maximum number of times a proposal can be extended
+    pub time_lock: u64,             // Delay between a proposal succeeding and it becoming executable
+    pub execution_window: u64,      // How long a proposal stays executable once unlocked (0 = no limit)
+    pub vault_count: u8,            // Number of indexed vaults created (vault 0 is the treasury)
+    pub module_count: u8,           // Number of registered modules
+    pub modules: [Pubkey; 4],       // Programs allowed to act through the treasury without a vote
 }
 
 impl Multisig {
-    pub const LEN: usize = 32 + 1 + (32 * 10) + 8 + 8 + 8 + 32 + 1 + 1;
+    pub const MAX_MODULES: usize = 4;
+    // Taken from the layout rather than summed by hand so `#[repr(C)]` padding is included
+    pub const LEN: usize = core::mem::size_of::<Self>();
 
     pub fn from_account_info_unchecked(account_info: &AccountInfo) -> &mut Self {
         unsafe {
@@ -32,3 +45,37 @@ impl Multisig {
         Ok(Self::from_account_info_unchecked(account_info))
     }
 }
+
+/// Clock field a multisig measures its voting windows in
+/// `proposal_expiry` and every proposal deadline are expressed in this unit
+#[repr(u8)]
+#[derive(Clone, Copy, PartialEq)]
+pub enum TimeUnit {
+    UnixTimestamp = 0, // Seconds, from `Clock::unix_timestamp` (default)
+    Slot = 1,          // From `Clock::slot`
+    Epoch = 2,         // From `Clock::epoch`
+}
+
+impl TimeUnit {
+    /// Returns the current time from the clock sysvar, in this unit
+    pub fn current(&self, clock: &Clock) -> u64 {
+        match self {
+            TimeUnit::UnixTimestamp => clock.unix_timestamp as u64,
+            TimeUnit::Slot => clock.slot,
+            TimeUnit::Epoch => clock.epoch,
+        }
+    }
+}
+
+impl TryFrom<&u8> for TimeUnit {
+    type Error = ProgramError;
+
+    fn try_from(value: &u8) -> Result<Self, Self::Error> {
+        match *value {
+            0 => Ok(TimeUnit::UnixTimestamp),
+            1 => Ok(TimeUnit::Slot),
+            2 => Ok(TimeUnit::Epoch),
+            _ => Err(ProgramError::InvalidInstructionData),
+        }
+    }
+}
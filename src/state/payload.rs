@@ -0,0 +1,640 @@
+use pinocchio::{account_info::AccountInfo, program_error::ProgramError, pubkey::Pubkey};
+
+/// Holds the serialized payload of a proposal (e.g. the instructions of a batch)
+/// The header below is followed by the raw payload bytes
+/// PDA: [b"payload", proposal]
+#[repr(C)]
+pub struct ProposalPayload {
+    pub proposal: Pubkey, // Proposal this payload belongs to
+    pub bump: u8,         // PDA bump for this payload account
+}
+
+impl ProposalPayload {
+    pub const LEN: usize = 32 + 1;
+
+    pub fn from_account_info_unchecked(account_info: &AccountInfo) -> &mut Self {
+        unsafe { &mut *(account_info.borrow_mut_data_unchecked().as_ptr() as *mut Self) }
+    }
+
+    pub fn from_account_info(account_info: &AccountInfo) -> Result<&mut Self, ProgramError> {
+        if account_info.data_len() < Self::LEN {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        Ok(Self::from_account_info_unchecked(account_info))
+    }
+
+    /// Returns the payload bytes stored after the header
+    pub fn data(account_info: &AccountInfo) -> &[u8] {
+        unsafe { &account_info.borrow_data_unchecked()[Self::LEN..] }
+    }
+}
+
+/// Payloads with a fixed layout. They are read by value because payload bytes
+/// (in instruction data or after the payload header) are not aligned, and are
+/// `#[repr(C, packed)]` so that `LEN` is exactly the size of the struct
+pub trait FixedPayload: Sized + Copy {
+    const LEN: usize;
+
+    fn from_bytes(bytes: &[u8]) -> Result<Self, ProgramError> {
+        if bytes.len() != Self::LEN {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+        Ok(unsafe { core::ptr::read_unaligned(bytes.as_ptr() as *const Self) })
+    }
+}
+
+/// Upgrades a program whose upgrade authority is the treasury
+#[repr(C, packed)]
+#[derive(Clone, Copy)]
+pub struct ProgramUpgrade {
+    pub program: Pubkey,      // Program to upgrade
+    pub program_data: Pubkey, // Its program data account
+    pub buffer: Pubkey,       // Buffer holding the new program (authority must be the treasury)
+    pub spill: Pubkey,        // Receives the buffer's lamports
+}
+
+impl FixedPayload for ProgramUpgrade {
+    const LEN: usize = 32 * 4;
+}
+
+/// Transfers the upgrade authority of a program held by the treasury
+#[repr(C, packed)]
+#[derive(Clone, Copy)]
+pub struct SetUpgradeAuthority {
+    pub program: Pubkey,       // Program whose authority changes
+    pub program_data: Pubkey,  // Its program data account
+    pub new_authority: Pubkey, // New upgrade authority (all zeros makes the program immutable)
+}
+
+impl FixedPayload for SetUpgradeAuthority {
+    const LEN: usize = 32 * 3;
+}
+
+/// Creates the next indexed vault of the multisig
+#[repr(C, packed)]
+#[derive(Clone, Copy)]
+pub struct CreateVault {
+    pub threshold: u64, // Approvals required for proposals targeting the vault
+}
+
+impl FixedPayload for CreateVault {
+    const LEN: usize = 8;
+}
+
+/// Creates a spending limit on the proposal's vault
+#[repr(C, packed)]
+#[derive(Clone, Copy)]
+pub struct CreateSpendingLimit {
+    pub mint: Pubkey,          // Token mint, or the default pubkey for SOL
+    pub amount: u64,           // Allowance per period
+    pub period: u8,            // See `SpendingPeriod`
+    pub member_count: u8,      // Number of entries in `members`
+    pub members: [Pubkey; 10], // Members allowed to use the limit
+}
+
+impl FixedPayload for CreateSpendingLimit {
+    const LEN: usize = 32 + 8 + 1 + 1 + (32 * 10);
+}
+
+/// Transfers SOL or tokens from the proposal's vault to a recipient
+#[repr(C, packed)]
+#[derive(Clone, Copy)]
+pub struct Transfer {
+    pub mint: Pubkey,      // Token mint, or the default pubkey for SOL
+    pub recipient: Pubkey, // Recipient wallet (owner of the destination token account for tokens)
+    pub amount: u64,       // Lamports, or token base units
+}
+
+impl FixedPayload for Transfer {
+    const LEN: usize = 32 + 32 + 8;
+}
+
+/// Changes the multisig's address book
+/// - action 0: add `recipient` (or update its cap) with a cap of `value` (0 = no cap)
+/// - action 1: remove `recipient`
+/// - action 2: set the threshold for transfers outside the book to `value`
+#[repr(C, packed)]
+#[derive(Clone, Copy)]
+pub struct UpdateAddressBook {
+    pub action: u8,
+    pub recipient: Pubkey,
+    pub value: u64,
+}
+
+impl FixedPayload for UpdateAddressBook {
+    const LEN: usize = 1 + 32 + 8;
+}
+
+/// Sets the rolling outflow cap of an asset on the proposal's vault
+#[repr(C, packed)]
+#[derive(Clone, Copy)]
+pub struct SetRateLimit {
+    pub mint: Pubkey,     // Token mint, or the default pubkey for SOL
+    pub max_outflow: u64, // Most that can leave the vault within any window (0 removes the cap)
+    pub window: u64,      // Length of the rolling window in seconds
+}
+
+impl FixedPayload for SetRateLimit {
+    const LEN: usize = 32 + 8 + 8;
+}
+
+/// Creates a recurring payment from the proposal's vault
+/// The schedule ends after `count` payments or once `end_time` has passed, whichever
+/// comes first (0 disables either bound, but not both)
+#[repr(C, packed)]
+#[derive(Clone, Copy)]
+pub struct CreatePaymentSchedule {
+    pub mint: Pubkey,      // Token mint, or the default pubkey for SOL
+    pub recipient: Pubkey, // Recipient wallet (owner of the destination token account for tokens)
+    pub amount: u64,       // Paid each interval (lamports, or token base units)
+    pub interval: u64,     // Seconds between payments
+    pub first_due: u64,    // Unix timestamp of the first payment (0 = on execution)
+    pub count: u64,        // Number of payments (0 = until `end_time`)
+    pub end_time: u64,     // No payment falls due after this unix timestamp (0 = after `count` payments)
+}
+
+impl FixedPayload for CreatePaymentSchedule {
+    const LEN: usize = 32 + 32 + 8 + 8 + 8 + 8 + 8;
+}
+
+/// Stops a payment schedule, so no further payment can be cranked
+#[repr(C, packed)]
+#[derive(Clone, Copy)]
+pub struct CancelPaymentSchedule {
+    pub schedule: Pubkey, // Payment schedule account to cancel
+}
+
+impl FixedPayload for CancelPaymentSchedule {
+    const LEN: usize = 32;
+}
+
+/// Escrows funds from the proposal's vault into a stream that vests linearly to a recipient
+#[repr(C, packed)]
+#[derive(Clone, Copy)]
+pub struct CreateStream {
+    pub mint: Pubkey,      // Token mint, or the default pubkey for SOL
+    pub recipient: Pubkey, // Recipient wallet (owner of the destination token account for tokens)
+    pub amount: u64,       // Total vested over the stream (lamports, or token base units)
+    pub start_time: u64,   // Unix timestamp vesting starts at (0 = on execution)
+    pub duration: u64,     // Seconds over which `amount` vests
+}
+
+impl FixedPayload for CreateStream {
+    const LEN: usize = 32 + 32 + 8 + 8 + 8;
+}
+
+/// Stops a stream, returning its unvested remainder to the proposal's vault
+#[repr(C, packed)]
+#[derive(Clone, Copy)]
+pub struct CancelStream {
+    pub stream: Pubkey, // Stream account to cancel
+}
+
+impl FixedPayload for CancelStream {
+    const LEN: usize = 32;
+}
+
+/// Escrows payment for a contractor from the proposal's vault, released milestone by milestone
+#[repr(C, packed)]
+#[derive(Clone, Copy)]
+pub struct CreateMilestoneEscrow {
+    pub mint: Pubkey,       // Token mint, or the default pubkey for SOL
+    pub contractor: Pubkey, // Paid as milestones are approved (owner of the destination token account for tokens)
+    pub deadline: u64,      // Unix timestamp after which unreleased funds can be clawed back
+    pub milestone_count: u8, // Number of entries in `amounts`
+    pub amounts: [u64; 8],  // Paid on approval of each milestone
+}
+
+impl FixedPayload for CreateMilestoneEscrow {
+    const LEN: usize = 32 + 32 + 8 + 1 + (8 * 8);
+}
+
+/// Claws back the unreleased funds of a milestone escrow past its deadline
+#[repr(C, packed)]
+#[derive(Clone, Copy)]
+pub struct CancelMilestoneEscrow {
+    pub escrow: Pubkey, // Milestone escrow account to cancel
+}
+
+impl FixedPayload for CancelMilestoneEscrow {
+    const LEN: usize = 32;
+}
+
+/// Allocates a budget from the proposal's vault to a set of owners
+#[repr(C, packed)]
+#[derive(Clone, Copy)]
+pub struct CreateBudget {
+    pub mint: Pubkey,         // Token mint, or the default pubkey for SOL
+    pub name: [u8; 32],       // Label of the budget (e.g. "marketing-q3"), zero padded
+    pub amount: u64,          // Total the owners can spend
+    pub expires_at: u64,      // Unix timestamp after which nothing can be spent
+    pub period: u64,          // Seconds after which the amount can be spent again (0 = once)
+    pub threshold: u64,       // Owner approvals needed for each spend
+    pub owner_count: u8,      // Number of entries in `owners`
+    pub owners: [Pubkey; 10], // Members who spend the budget
+}
+
+impl FixedPayload for CreateBudget {
+    const LEN: usize = 32 + 32 + 8 + 8 + 8 + 8 + 1 + (32 * 10);
+}
+
+/// Creates a stake account funded by the proposal's vault, which becomes its stake and
+/// withdraw authority
+#[repr(C, packed)]
+#[derive(Clone, Copy)]
+pub struct CreateStake {
+    pub lamports: u64, // Funds moved into the stake account (including its rent exemption)
+}
+
+impl FixedPayload for CreateStake {
+    const LEN: usize = 8;
+}
+
+/// Delegates a stake account of the proposal's vault to a validator
+#[repr(C, packed)]
+#[derive(Clone, Copy)]
+pub struct DelegateStake {
+    pub stake: Pubkey, // Stake account to delegate
+    pub vote: Pubkey,  // Vote account of the validator
+}
+
+impl FixedPayload for DelegateStake {
+    const LEN: usize = 32 + 32;
+}
+
+/// Deactivates a stake account of the proposal's vault
+#[repr(C, packed)]
+#[derive(Clone, Copy)]
+pub struct DeactivateStake {
+    pub stake: Pubkey, // Stake account to deactivate
+}
+
+impl FixedPayload for DeactivateStake {
+    const LEN: usize = 32;
+}
+
+/// Withdraws inactive lamports from a stake account back into the proposal's vault
+#[repr(C, packed)]
+#[derive(Clone, Copy)]
+pub struct WithdrawStake {
+    pub stake: Pubkey, // Stake account to withdraw from
+    pub lamports: u64, // Amount to withdraw
+}
+
+impl FixedPayload for WithdrawStake {
+    const LEN: usize = 32 + 8;
+}
+
+/// Records that the multisig approved a message (e.g. off-chain terms)
+#[repr(C, packed)]
+#[derive(Clone, Copy)]
+pub struct ApproveMessage {
+    pub hash: [u8; 32], // Hash of the approved message
+}
+
+impl FixedPayload for ApproveMessage {
+    const LEN: usize = 32;
+}
+
+/// Changes the programs batches of the multisig may invoke
+/// - action 0: allow `program`
+/// - action 1: disallow `program`
+#[repr(C, packed)]
+#[derive(Clone, Copy)]
+pub struct UpdateProgramAllowlist {
+    pub action: u8,
+    pub program: Pubkey,
+}
+
+impl FixedPayload for UpdateProgramAllowlist {
+    const LEN: usize = 1 + 32;
+}
+
+/// Sets the guard program called around proposal execution (default pubkey removes it)
+#[repr(C, packed)]
+#[derive(Clone, Copy)]
+pub struct SetGuard {
+    pub program: Pubkey,
+}
+
+impl FixedPayload for SetGuard {
+    const LEN: usize = 32;
+}
+
+/// Changes the modules registered on the multisig
+/// - action 0: register `program`
+/// - action 1: unregister `program`
+#[repr(C, packed)]
+#[derive(Clone, Copy)]
+pub struct UpdateModules {
+    pub action: u8,
+    pub program: Pubkey,
+}
+
+impl FixedPayload for UpdateModules {
+    const LEN: usize = 1 + 32;
+}
+
+/// Decides how a multisig votes on a proposal of a parent multisig it is a member of
+#[repr(C, packed)]
+#[derive(Clone, Copy)]
+pub struct ParentVote {
+    pub proposal: Pubkey, // Parent proposal to vote on
+    pub vote: u8,         // 1 (yes) or 0 (no)
+}
+
+impl FixedPayload for ParentVote {
+    const LEN: usize = 32 + 1;
+}
+
+/// Zero-copy view over a serialized batch of instructions
+/// Layout:
+/// - 1 byte: number of account keys
+/// - 32 bytes per key: accounts (and programs) referenced directly by the batch
+/// - 1 byte: number of address lookup tables
+/// - per lookup table:
+///   - 32 bytes: address of the lookup table
+///   - 1 byte: number of addresses loaded from it
+///   - 1 byte per address: its index in the lookup table
+/// - 1 byte: number of instructions
+/// - per instruction:
+///   - 1 byte: index of the program id in the account keys
+///   - 1 byte: number of accounts
+///   - 2 bytes per account: index in the account keys, then 1 if writable or 0 if not
+///   - 2 bytes: data length (u16)
+///   - the instruction data
+///
+/// Instructions index into the account keys followed by every address loaded
+/// from the lookup tables, in order (like a v0 message)
+pub struct Batch<'a> {
+    pub account_keys: &'a [Pubkey],
+    pub lookup_count: u8,
+    pub loaded_count: usize, // Total number of addresses loaded from lookup tables
+    lookups: &'a [u8],
+    pub instruction_count: u8,
+    instructions: &'a [u8],
+}
+
+pub struct AddressTableLookup<'a> {
+    pub table: &'a Pubkey,
+    pub indices: &'a [u8],
+}
+
+pub struct BatchInstruction<'a> {
+    pub program_index: u8,
+    pub accounts: &'a [[u8; 2]], // (key index, is_writable)
+    pub data: &'a [u8],
+}
+
+impl<'a> Batch<'a> {
+    pub fn parse(bytes: &'a [u8]) -> Result<Self, ProgramError> {
+        let (&key_count, rest) = bytes.split_first().ok_or(ProgramError::InvalidInstructionData)?;
+        let keys_len = key_count as usize * 32;
+        if rest.len() < keys_len {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+        let (keys, rest) = rest.split_at(keys_len);
+        let account_keys =
+            unsafe { core::slice::from_raw_parts(keys.as_ptr() as *const Pubkey, key_count as usize) };
+
+        let (&lookup_count, rest) = rest.split_first().ok_or(ProgramError::InvalidInstructionData)?;
+        let mut lookups_len = 0;
+        let mut loaded_count = 0;
+        for _ in 0..lookup_count {
+            let index_count = *rest
+                .get(lookups_len + 32)
+                .ok_or(ProgramError::InvalidInstructionData)? as usize;
+            lookups_len += 32 + 1 + index_count;
+            loaded_count += index_count;
+        }
+        if rest.len() < lookups_len {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+        let (lookups, rest) = rest.split_at(lookups_len);
+
+        // Instructions reference accounts with a single byte
+        if key_count as usize + loaded_count > u8::MAX as usize + 1 {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+
+        let (&instruction_count, instructions) =
+            rest.split_first().ok_or(ProgramError::InvalidInstructionData)?;
+
+        let batch = Self {
+            account_keys,
+            lookup_count,
+            loaded_count,
+            lookups,
+            instruction_count,
+            instructions,
+        };
+
+        // Walk every instruction once so a malformed batch is rejected up front
+        let mut iter = batch.instructions();
+        for instruction in iter.by_ref() {
+            instruction?;
+        }
+        if !iter.remaining.is_empty() {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+
+        Ok(batch)
+    }
+
+    /// Iterates over the address lookup tables (already bounds checked by `parse`)
+    pub fn lookups(&self) -> impl Iterator<Item = AddressTableLookup<'a>> {
+        let mut remaining = self.lookups;
+        (0..self.lookup_count).map(move |_| {
+            let (table, rest) = remaining.split_at(32);
+            let index_count = rest[0] as usize;
+            let (indices, rest) = rest[1..].split_at(index_count);
+            remaining = rest;
+            AddressTableLookup {
+                table: unsafe { &*(table.as_ptr() as *const Pubkey) },
+                indices,
+            }
+        })
+    }
+
+    pub fn instructions(&self) -> BatchInstructions<'a> {
+        BatchInstructions {
+            remaining: self.instructions,
+            left: self.instruction_count,
+            key_count: self.account_keys.len() + self.loaded_count,
+        }
+    }
+}
+
+pub struct BatchInstructions<'a> {
+    remaining: &'a [u8],
+    left: u8,
+    key_count: usize,
+}
+
+impl<'a> BatchInstructions<'a> {
+    fn read(&mut self) -> Result<BatchInstruction<'a>, ProgramError> {
+        let [program_index, account_count, rest @ ..] = self.remaining else {
+            return Err(ProgramError::InvalidInstructionData);
+        };
+        let metas_len = *account_count as usize * 2;
+        if rest.len() < metas_len + 2 {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+        let (metas, rest) = rest.split_at(metas_len);
+        let data_len = u16::from_le_bytes([rest[0], rest[1]]) as usize;
+        let rest = &rest[2..];
+        if rest.len() < data_len {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+        let (data, rest) = rest.split_at(data_len);
+        let accounts = unsafe {
+            core::slice::from_raw_parts(metas.as_ptr() as *const [u8; 2], *account_count as usize)
+        };
+
+        if *program_index as usize >= self.key_count
+            || accounts.iter().any(|[index, _]| *index as usize >= self.key_count)
+        {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+
+        self.remaining = rest;
+        Ok(BatchInstruction {
+            program_index: *program_index,
+            accounts,
+            data,
+        })
+    }
+}
+
+impl<'a> Iterator for BatchInstructions<'a> {
+    type Item = Result<BatchInstruction<'a>, ProgramError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.left == 0 {
+            return None;
+        }
+        let instruction = self.read();
+        // Stop at the first malformed instruction
+        self.left = if instruction.is_ok() { self.left - 1 } else { 0 };
+        Some(instruction)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Program index, account metas (index, writable), and data of a batch instruction
+    type TestInstruction<'a> = (u8, &'a [[u8; 2]], &'a [u8]);
+
+    /// Serializes a batch with the layout documented on `Batch`
+    fn batch_bytes(keys: &[Pubkey], lookups: &[(Pubkey, &[u8])], instructions: &[TestInstruction]) -> Vec<u8> {
+        let mut bytes = vec![keys.len() as u8];
+        for key in keys {
+            bytes.extend_from_slice(key);
+        }
+        bytes.push(lookups.len() as u8);
+        for (table, indices) in lookups {
+            bytes.extend_from_slice(table);
+            bytes.push(indices.len() as u8);
+            bytes.extend_from_slice(indices);
+        }
+        bytes.push(instructions.len() as u8);
+        for (program_index, accounts, data) in instructions {
+            bytes.push(*program_index);
+            bytes.push(accounts.len() as u8);
+            for meta in accounts.iter() {
+                bytes.extend_from_slice(meta);
+            }
+            bytes.extend_from_slice(&(data.len() as u16).to_le_bytes());
+            bytes.extend_from_slice(data);
+        }
+        bytes
+    }
+
+    #[test]
+    fn parses_keys_lookups_and_instructions() {
+        let bytes = batch_bytes(
+            &[[1; 32], [2; 32]],
+            &[([9; 32], &[4, 7])],
+            &[(1, &[[0, 1], [3, 0]], &[0xAA, 0xBB]), (3, &[], &[])],
+        );
+        let batch = Batch::parse(&bytes).unwrap();
+
+        assert_eq!(batch.account_keys, &[[1; 32], [2; 32]]);
+        assert_eq!(batch.lookup_count, 1);
+        assert_eq!(batch.loaded_count, 2);
+        assert_eq!(batch.instruction_count, 2);
+
+        let lookup = batch.lookups().next().unwrap();
+        assert_eq!(lookup.table, &[9; 32]);
+        assert_eq!(lookup.indices, &[4, 7]);
+
+        let instructions: Vec<_> = batch.instructions().map(Result::unwrap).collect();
+        assert_eq!(instructions[0].program_index, 1);
+        assert_eq!(instructions[0].accounts, &[[0, 1], [3, 0]]);
+        assert_eq!(instructions[0].data, &[0xAA, 0xBB]);
+        assert_eq!(instructions[1].program_index, 3);
+        assert!(instructions[1].accounts.is_empty());
+    }
+
+    #[test]
+    fn indices_may_reach_the_last_loaded_address_but_not_beyond() {
+        // 1 key + 2 loaded addresses: valid indices are 0..=2
+        let last = batch_bytes(&[[1; 32]], &[([9; 32], &[0, 1])], &[(2, &[[2, 0]], &[])]);
+        assert!(Batch::parse(&last).is_ok());
+
+        let program_out_of_bounds =
+            batch_bytes(&[[1; 32]], &[([9; 32], &[0, 1])], &[(3, &[], &[])]);
+        assert!(matches!(
+            Batch::parse(&program_out_of_bounds),
+            Err(ProgramError::InvalidInstructionData)
+        ));
+
+        let account_out_of_bounds =
+            batch_bytes(&[[1; 32]], &[([9; 32], &[0, 1])], &[(0, &[[3, 1]], &[])]);
+        assert!(matches!(
+            Batch::parse(&account_out_of_bounds),
+            Err(ProgramError::InvalidInstructionData)
+        ));
+    }
+
+    #[test]
+    fn rejects_more_accounts_than_a_byte_can_index() {
+        let keys = [[1; 32]; 200];
+        let indices = [0u8; 56];
+        assert!(Batch::parse(&batch_bytes(&keys, &[([9; 32], &indices)], &[])).is_ok());
+
+        let indices = [0u8; 57];
+        assert!(matches!(
+            Batch::parse(&batch_bytes(&keys, &[([9; 32], &indices)], &[])),
+            Err(ProgramError::InvalidInstructionData)
+        ));
+    }
+
+    #[test]
+    fn rejects_truncated_or_trailing_bytes() {
+        let bytes = batch_bytes(&[[1; 32]], &[([9; 32], &[0, 1])], &[(0, &[[1, 0]], &[1, 2, 3])]);
+
+        for len in 0..bytes.len() {
+            assert!(
+                Batch::parse(&bytes[..len]).is_err(),
+                "accepted a batch truncated to {} bytes",
+                len
+            );
+        }
+
+        let mut trailing = bytes;
+        trailing.push(0);
+        assert!(matches!(Batch::parse(&trailing), Err(ProgramError::InvalidInstructionData)));
+    }
+
+    #[test]
+    fn rejects_instruction_data_longer_than_the_batch() {
+        let mut bytes = batch_bytes(&[[1; 32]], &[], &[(0, &[], &[1, 2])]);
+        // Claim 3 bytes of data while only 2 follow
+        let len_at = bytes.len() - 4;
+        bytes[len_at..len_at + 2].copy_from_slice(&3u16.to_le_bytes());
+        assert!(matches!(Batch::parse(&bytes), Err(ProgramError::InvalidInstructionData)));
+    }
+}
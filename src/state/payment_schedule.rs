@@ -0,0 +1,116 @@
+use pinocchio::{account_info::AccountInfo, program_error::ProgramError, pubkey::Pubkey};
+
+/// A recurring payment out of a vault that anyone can crank once it falls due
+/// Created by a `CreatePaymentSchedule` proposal and paid from that proposal's vault
+/// until it finishes or a `CancelPaymentSchedule` proposal stops it
+/// PDA: [b"payment_schedule", proposal]
+#[repr(C)]
+pub struct PaymentSchedule {
+    pub proposal: Pubkey,   // Proposal that created the schedule (seed of this PDA)
+    pub multisig: Pubkey,   // Multisig the schedule belongs to
+    pub mint: Pubkey,       // Token mint, or the default pubkey for SOL
+    pub recipient: Pubkey,  // Recipient wallet (owner of the destination token account for tokens)
+    pub amount: u64,        // Paid each interval
+    pub interval: u64,      // Seconds between payments
+    pub next_due: u64,      // Unix timestamp from which the next payment can be cranked
+    pub count: u64,         // Number of payments (0 = until `end_time`)
+    pub end_time: u64,      // No payment falls due after this unix timestamp (0 = no end date)
+    pub payments_made: u64, // Payments cranked so far
+    pub proposal_id: u64,   // Proposal that created the schedule
+    pub vault_index: u8,    // Vault the payments are taken from (0 = treasury)
+    pub vault_bump: u8,     // Bump of that vault PDA
+    pub bump: u8,           // PDA bump for this account
+    pub cancelled: u8,      // 1 once a `CancelPaymentSchedule` proposal has stopped the schedule
+}
+
+impl PaymentSchedule {
+    pub const LEN: usize = core::mem::size_of::<Self>();
+
+    pub fn from_account_info_unchecked(account_info: &AccountInfo) -> &mut Self {
+        unsafe { &mut *(account_info.borrow_mut_data_unchecked().as_ptr() as *mut Self) }
+    }
+
+    pub fn from_account_info(account_info: &AccountInfo) -> Result<&mut Self, ProgramError> {
+        if account_info.data_len() < Self::LEN {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        Ok(Self::from_account_info_unchecked(account_info))
+    }
+
+    /// Whether every payment of the schedule has been made
+    pub fn is_finished(&self) -> bool {
+        (self.count != 0 && self.payments_made >= self.count)
+            || (self.end_time != 0 && self.next_due > self.end_time)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn schedule_with(count: u64, end_time: u64) -> PaymentSchedule {
+        PaymentSchedule {
+            proposal: Pubkey::default(),
+            multisig: Pubkey::default(),
+            mint: Pubkey::default(),
+            recipient: Pubkey::default(),
+            amount: 100,
+            interval: 10,
+            next_due: 1_000,
+            count,
+            end_time,
+            payments_made: 0,
+            proposal_id: 0,
+            vault_index: 0,
+            vault_bump: 0,
+            bump: 0,
+            cancelled: 0,
+        }
+    }
+
+    /// Cranks `schedule` as the program does, one interval per payment
+    fn pay(schedule: &mut PaymentSchedule) {
+        schedule.next_due += schedule.interval;
+        schedule.payments_made += 1;
+    }
+
+    #[test]
+    fn a_schedule_with_a_count_finishes_after_that_many_payments() {
+        let mut schedule = schedule_with(3, 0);
+        for _ in 0..3 {
+            assert!(!schedule.is_finished());
+            pay(&mut schedule);
+        }
+        assert!(schedule.is_finished());
+    }
+
+    #[test]
+    fn a_schedule_with_an_end_date_pays_every_interval_due_by_then() {
+        // Due at 1_000, 1_010 and 1_020, the next one (1_030) falls after the end date
+        let mut schedule = schedule_with(0, 1_025);
+        for _ in 0..3 {
+            assert!(!schedule.is_finished());
+            pay(&mut schedule);
+        }
+        assert!(schedule.is_finished());
+        assert_eq!(schedule.payments_made, 3);
+
+        // A payment due exactly at the end date is still made
+        let mut schedule = schedule_with(0, 1_020);
+        pay(&mut schedule);
+        pay(&mut schedule);
+        assert!(!schedule.is_finished());
+    }
+
+    #[test]
+    fn the_first_bound_reached_finishes_the_schedule() {
+        let mut schedule = schedule_with(2, 10_000);
+        pay(&mut schedule);
+        pay(&mut schedule);
+        assert!(schedule.is_finished());
+
+        let mut schedule = schedule_with(100, 1_005);
+        pay(&mut schedule);
+        assert!(schedule.is_finished());
+    }
+}
@@ -0,0 +1,34 @@
+use pinocchio::{account_info::AccountInfo, program_error::ProgramError, pubkey::Pubkey};
+
+/// Programs the instructions of a multisig's batches may invoke
+/// Multisigs without one are limited to the default programs (see `DEFAULT_ALLOWED_PROGRAMS`)
+/// PDA: [b"program_allowlist", multisig]
+#[repr(C)]
+pub struct ProgramAllowlist {
+    pub multisig: Pubkey,        // Multisig the allowlist belongs to
+    pub programs: [Pubkey; 16],  // Allowed program ids
+    pub program_count: u8,       // Number of entries in use
+    pub bump: u8,                // PDA bump for this account
+}
+
+impl ProgramAllowlist {
+    pub const MAX_PROGRAMS: usize = 16;
+    pub const LEN: usize = core::mem::size_of::<Self>();
+
+    pub fn from_account_info_unchecked(account_info: &AccountInfo) -> &mut Self {
+        unsafe { &mut *(account_info.borrow_mut_data_unchecked().as_ptr() as *mut Self) }
+    }
+
+    pub fn from_account_info(account_info: &AccountInfo) -> Result<&mut Self, ProgramError> {
+        if account_info.data_len() < Self::LEN {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        Ok(Self::from_account_info_unchecked(account_info))
+    }
+
+    pub fn position(&self, program: &Pubkey) -> Option<usize> {
+        self.programs[..self.program_count as usize]
+            .iter()
+            .position(|allowed| allowed == program)
+    }
+}
@@ -1,19 +1,48 @@
 use pinocchio::{account_info::AccountInfo, program_error::ProgramError, pubkey::Pubkey};
 
+use super::{
+    ApproveMessage, Batch, CancelMilestoneEscrow, CancelPaymentSchedule, CancelStream, CreateBudget,
+    CreateMilestoneEscrow, CreatePaymentSchedule, CreateSpendingLimit, CreateStake, CreateStream,
+    CreateVault, DeactivateStake, DelegateStake, FixedPayload, ParentVote, ProgramUpgrade, SetGuard,
+    SetUpgradeAuthority, TimeUnit, SetRateLimit, Transfer, UpdateAddressBook, UpdateModules,
+    UpdateProgramAllowlist, WithdrawStake,
+};
+use crate::utils::sha256;
+
 #[repr(C)]
 #[derive(PartialEq)]
 pub struct Proposal {
     pub creator: Pubkey,              // Address that created the proposal
     pub id: u64,                  // Unique ID of the proposal
-    pub expiration_time: u64,     // When the proposal expires
+    pub expiration_time: u64,     // When the proposal expires (in `time_unit`)
     pub status: ProposalStatus,   // Current state of the proposal
     pub voter_keys: [Pubkey; 20], // Eligible voter public keys
     pub votes: [u8; 20],          // 1 (yes), 0 (no), or 255 (not voted) // 255 is the maximum value for a u8 often used as "not set" value
     pub created_at: u64,          // Timestamp of proposal creation
+    pub voting_starts_at: u64,    // When voting opens (in `time_unit`)
+    pub time_unit: TimeUnit,      // Unit of voting_starts_at / expiration_time, fixed at creation
+    pub multisig: Pubkey,         // Multisig this proposal belongs to
+    pub quiet_period: u64,        // Snapshot of the multisig's quiet period at creation
+    pub quiet_extension: u64,     // Snapshot of the multisig's quiet extension at creation
+    pub max_extensions: u8,       // Snapshot of the multisig's max extensions at creation
+    pub extensions_used: u8,      // Number of times the deadline has been extended
+    pub time_lock: u64,           // Snapshot of the multisig's time lock at creation
+    pub executable_after: u64,    // Earliest time the proposal can be executed (set once it succeeds)
+    pub execution_window: u64,    // Snapshot of the multisig's execution window at creation
+    pub execution_deadline: u64,  // Latest time the proposal can be executed (0 = no limit)
+    pub kind: ProposalKind,       // What the proposal does once executed
+    pub threshold: u64,           // Approvals required, from the target vault at creation
+    pub vault_index: u8,          // Vault the proposal acts on (0 = treasury)
+    pub vault_bump: u8,           // Bump of the target vault PDA
+    pub payload_hash: [u8; 32],   // SHA-256 of the payload (of no bytes for Standard proposals)
+    pub bump: u8,                 // Bump of the proposal PDA
 }
 
 impl Proposal {
-    pub const LEN: usize = 8 + 8 + 1 + (32 * 20) + 20 + 8 + 32;
+    // Taken from the layout rather than summed by hand so `#[repr(C)]` padding is included
+    pub const LEN: usize = core::mem::size_of::<Self>();
+    // Size of proposals created before the fields after `created_at` were added
+    pub const LEGACY_LEN: usize = 8 + 8 + 1 + (32 * 20) + 20 + 8 + 32;
 
     pub fn from_account_info_unchecked(account_info: &AccountInfo) -> &mut Self {
         unsafe { &mut *(account_info.borrow_mut_data_unchecked().as_ptr() as *mut Self) }
@@ -27,16 +56,27 @@ impl Proposal {
         }
         Ok(Self::from_account_info_unchecked(account_info))
     }
+
+    /// Whether this `ParentVote` proposal approved casting `vote` on `parent_proposal`
+    /// Only the payload hash is stored, so the payload it must have had is rebuilt and hashed
+    pub fn decides_parent_vote(&self, parent_proposal: &Pubkey, vote: u8) -> bool {
+        let mut payload = [0u8; ParentVote::LEN];
+        payload[..32].copy_from_slice(parent_proposal);
+        payload[32] = vote;
+        sha256(&payload) == self.payload_hash
+    }
 }
 
 #[repr(u8)]
-#[derive(PartialEq)]
+#[derive(Clone, Copy, PartialEq)]
 pub enum ProposalStatus {
     Draft = 0,     // Not Actually using it (will use it V2)
     Active = 1,    // Currently open for voting
     Failed = 2,    // Did not meet threshold or expired
     Succeeded = 3, // Met threshold and succeeded
     Cancelled = 4, // Manually cancelled before conclusion
+    Expired = 5,   // Succeeded but not executed within the execution window
+    Executed = 6,  // Succeeded and its payload has been executed
 }
 
 impl TryFrom<&u8> for ProposalStatus {
@@ -49,7 +89,159 @@ impl TryFrom<&u8> for ProposalStatus {
             2 => Ok(ProposalStatus::Failed),
             3 => Ok(ProposalStatus::Succeeded),
             4 => Ok(ProposalStatus::Cancelled),
+            5 => Ok(ProposalStatus::Expired),
+            6 => Ok(ProposalStatus::Executed),
+            _ => Err(ProgramError::InvalidInstructionData),
+        }
+    }
+}
+
+#[repr(u8)]
+#[derive(Clone, Copy, PartialEq)]
+pub enum ProposalKind {
+    Standard = 0,            // Signalling only, nothing to execute
+    Batch = 1,               // Ordered list of instructions executed atomically by the treasury
+    ProgramUpgrade = 2,      // Upgrade a program whose upgrade authority is the treasury
+    SetUpgradeAuthority = 3, // Hand over (or revoke) the upgrade authority of such a program
+    CreateVault = 4,         // Create the next indexed vault
+    CreateSpendingLimit = 5, // Let members withdraw up to a limit from the vault without a vote
+    Transfer = 6,            // Send SOL or tokens from the vault, subject to the address book
+    UpdateAddressBook = 7,   // Change the approved recipients of the multisig
+    SetRateLimit = 8,        // Cap what can leave the vault within a rolling window
+    CreatePaymentSchedule = 9, // Pay a recipient from the vault at a fixed interval
+    CreateStream = 10,       // Escrow funds from the vault that vest linearly to a recipient
+    CancelStream = 11,       // Stop a stream and return its unvested funds to the vault
+    CreateMilestoneEscrow = 12, // Escrow a contractor's pay, released per approved milestone
+    CancelMilestoneEscrow = 13, // Claw back unreleased escrow funds after the deadline
+    CreateBudget = 14,       // Let a set of owners spend from the vault with their own threshold
+    CreateStake = 15,        // Move vault SOL into a new stake account controlled by the vault
+    DelegateStake = 16,      // Delegate a vault stake account to a validator
+    DeactivateStake = 17,    // Deactivate a vault stake account
+    WithdrawStake = 18,      // Withdraw inactive stake back into the vault
+    ApproveMessage = 19,     // Record that the multisig approved a message hash
+    UpdateProgramAllowlist = 20, // Change the programs batches may invoke (needs a supermajority)
+    SetGuard = 21,           // Set or remove the guard program called around execution
+    UpdateModules = 22,      // Register or unregister a module acting through the treasury
+    ParentVote = 23,         // Decide the multisig's vote on a parent multisig's proposal
+    CancelPaymentSchedule = 24, // Stop a payment schedule before its last payment
+}
+
+impl ProposalKind {
+    /// Checks that a payload is well formed for this kind of proposal
+    pub fn validate_payload(&self, payload: &[u8]) -> Result<(), ProgramError> {
+        match self {
+            ProposalKind::Standard if payload.is_empty() => Ok(()),
+            ProposalKind::Standard => Err(ProgramError::InvalidInstructionData),
+            ProposalKind::Batch => Batch::parse(payload).map(|_| ()),
+            ProposalKind::ProgramUpgrade => ProgramUpgrade::from_bytes(payload).map(|_| ()),
+            ProposalKind::SetUpgradeAuthority => SetUpgradeAuthority::from_bytes(payload).map(|_| ()),
+            ProposalKind::CreateVault => CreateVault::from_bytes(payload).map(|_| ()),
+            ProposalKind::CreateSpendingLimit => CreateSpendingLimit::from_bytes(payload).map(|_| ()),
+            ProposalKind::Transfer => Transfer::from_bytes(payload).map(|_| ()),
+            ProposalKind::UpdateAddressBook => UpdateAddressBook::from_bytes(payload).map(|_| ()),
+            ProposalKind::SetRateLimit => SetRateLimit::from_bytes(payload).map(|_| ()),
+            ProposalKind::CreatePaymentSchedule => CreatePaymentSchedule::from_bytes(payload).map(|_| ()),
+            ProposalKind::CreateStream => CreateStream::from_bytes(payload).map(|_| ()),
+            ProposalKind::CancelStream => CancelStream::from_bytes(payload).map(|_| ()),
+            ProposalKind::CreateMilestoneEscrow => CreateMilestoneEscrow::from_bytes(payload).map(|_| ()),
+            ProposalKind::CancelMilestoneEscrow => CancelMilestoneEscrow::from_bytes(payload).map(|_| ()),
+            ProposalKind::CreateBudget => CreateBudget::from_bytes(payload).map(|_| ()),
+            ProposalKind::CreateStake => CreateStake::from_bytes(payload).map(|_| ()),
+            ProposalKind::DelegateStake => DelegateStake::from_bytes(payload).map(|_| ()),
+            ProposalKind::DeactivateStake => DeactivateStake::from_bytes(payload).map(|_| ()),
+            ProposalKind::WithdrawStake => WithdrawStake::from_bytes(payload).map(|_| ()),
+            ProposalKind::ApproveMessage => ApproveMessage::from_bytes(payload).map(|_| ()),
+            ProposalKind::UpdateProgramAllowlist => UpdateProgramAllowlist::from_bytes(payload).map(|_| ()),
+            ProposalKind::SetGuard => SetGuard::from_bytes(payload).map(|_| ()),
+            ProposalKind::UpdateModules => UpdateModules::from_bytes(payload).map(|_| ()),
+            ProposalKind::ParentVote => ParentVote::from_bytes(payload).map(|_| ()),
+            ProposalKind::CancelPaymentSchedule => CancelPaymentSchedule::from_bytes(payload).map(|_| ()),
+        }
+    }
+}
+
+impl TryFrom<&u8> for ProposalKind {
+    type Error = ProgramError;
+
+    fn try_from(value: &u8) -> Result<Self, Self::Error> {
+        match *value {
+            0 => Ok(ProposalKind::Standard),
+            1 => Ok(ProposalKind::Batch),
+            2 => Ok(ProposalKind::ProgramUpgrade),
+            3 => Ok(ProposalKind::SetUpgradeAuthority),
+            4 => Ok(ProposalKind::CreateVault),
+            5 => Ok(ProposalKind::CreateSpendingLimit),
+            6 => Ok(ProposalKind::Transfer),
+            7 => Ok(ProposalKind::UpdateAddressBook),
+            8 => Ok(ProposalKind::SetRateLimit),
+            9 => Ok(ProposalKind::CreatePaymentSchedule),
+            10 => Ok(ProposalKind::CreateStream),
+            11 => Ok(ProposalKind::CancelStream),
+            12 => Ok(ProposalKind::CreateMilestoneEscrow),
+            13 => Ok(ProposalKind::CancelMilestoneEscrow),
+            14 => Ok(ProposalKind::CreateBudget),
+            15 => Ok(ProposalKind::CreateStake),
+            16 => Ok(ProposalKind::DelegateStake),
+            17 => Ok(ProposalKind::DeactivateStake),
+            18 => Ok(ProposalKind::WithdrawStake),
+            19 => Ok(ProposalKind::ApproveMessage),
+            20 => Ok(ProposalKind::UpdateProgramAllowlist),
+            21 => Ok(ProposalKind::SetGuard),
+            22 => Ok(ProposalKind::UpdateModules),
+            23 => Ok(ProposalKind::ParentVote),
+            24 => Ok(ProposalKind::CancelPaymentSchedule),
             _ => Err(ProgramError::InvalidInstructionData),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Zeroed, 8-byte aligned account data of `Proposal::LEN` bytes, like a new account
+    fn account_data() -> Vec<u64> {
+        vec![0u64; Proposal::LEN.div_ceil(8)]
+    }
+
+    /// Only the account's bytes are persisted: copies exactly `LEN` of them into a fresh account
+    fn persisted(data: &[u64]) -> Vec<u64> {
+        let mut persisted = account_data();
+        unsafe {
+            core::ptr::copy_nonoverlapping(
+                data.as_ptr() as *const u8,
+                persisted.as_mut_ptr() as *mut u8,
+                Proposal::LEN,
+            )
+        };
+        persisted
+    }
+
+    #[test]
+    fn parent_vote_decides_only_its_own_vote() {
+        let parent = [0x11; 32];
+
+        // The child multisig proposes the vote: the payload is validated, then only its hash is kept
+        let payload = [parent.as_ref(), &[1]].concat();
+        assert!(ProposalKind::ParentVote.validate_payload(&payload).is_ok());
+        let mut data = account_data();
+        unsafe {
+            let proposal = &mut *(data.as_mut_ptr() as *mut Proposal);
+            proposal.kind = ProposalKind::ParentVote;
+            proposal.payload_hash = sha256(&payload);
+        }
+
+        // The child's members approve it
+        let mut data = persisted(&data);
+        unsafe { (*(data.as_mut_ptr() as *mut Proposal)).status = ProposalStatus::Succeeded };
+
+        // The vote is then cast on the parent proposal with the child proposal
+        let data = persisted(&data);
+        let proposal = unsafe { &*(data.as_ptr() as *const Proposal) };
+        assert!(proposal.kind == ProposalKind::ParentVote);
+        assert!(proposal.status == ProposalStatus::Succeeded);
+        assert!(proposal.decides_parent_vote(&parent, 1));
+        assert!(!proposal.decides_parent_vote(&parent, 0));
+        assert!(!proposal.decides_parent_vote(&[0x22; 32], 1));
+    }
+}
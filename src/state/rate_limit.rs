@@ -0,0 +1,205 @@
+use pinocchio::{account_info::AccountInfo, program_error::ProgramError, pubkey::Pubkey};
+
+/// Rolling-window cap on what can leave a vault, per asset
+/// Enforced whenever funds leave the vault (proposal execution, spending limits)
+/// PDA: [b"rate_limit", multisig, vault_index]
+#[repr(C)]
+pub struct RateLimit {
+    pub multisig: Pubkey,                 // Multisig the vault belongs to
+    pub limits: [AssetRateLimit; 8],      // Caps per asset (assets not listed are not limited)
+    pub vault_index: u8,                  // Vault the caps apply to (0 = treasury)
+    pub limit_count: u8,                  // Number of entries in use
+    pub bump: u8,                         // PDA bump for this account
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct AssetRateLimit {
+    pub mint: Pubkey,      // Token mint, or the default pubkey for SOL
+    pub max_outflow: u64,  // Most that can leave the vault within any window
+    pub window: u64,       // Length of the rolling window in seconds
+    pub buckets: [u64; 8], // Outflow per sub-window, as a ring indexed by sub-window number
+    pub last_bucket: u64,  // Number (timestamp / sub-window length) of the latest sub-window
+}
+
+impl AssetRateLimit {
+    pub const BUCKETS: usize = 8;
+
+    /// Sub-windows are a seventh of the window, so the eight buckets cover at least the
+    /// whole window whatever the current time: no span of `window` seconds can see more
+    /// than `max_outflow` leave, at the cost of counting outflow up to a seventh longer
+    fn bucket_len(&self) -> u64 {
+        self.window.div_ceil(Self::BUCKETS as u64 - 1).max(1)
+    }
+
+    /// Drops the sub-windows that ended more than a window before `now`
+    /// Returns the number of the current sub-window
+    fn advance(&mut self, now: u64) -> u64 {
+        // A clock going backwards keeps counting into the latest sub-window
+        let current = (now / self.bucket_len()).max(self.last_bucket);
+        let stale = (current - self.last_bucket).min(Self::BUCKETS as u64);
+        for number in current + 1 - stale..=current {
+            self.buckets[(number % Self::BUCKETS as u64) as usize] = 0;
+        }
+        self.last_bucket = current;
+        current
+    }
+
+    /// Outflow counted against the window ending at the latest sub-window
+    pub fn used(&self) -> u64 {
+        self.buckets.iter().fold(0u64, |total, amount| total.saturating_add(*amount))
+    }
+
+    /// Changes the window, keeping the outflow counted so far in the current sub-window
+    pub fn set_window(&mut self, window: u64, now: u64) {
+        self.advance(now);
+        let used = self.used();
+        self.window = window;
+        self.buckets = [0; Self::BUCKETS];
+        self.last_bucket = now / self.bucket_len();
+        self.buckets[(self.last_bucket % Self::BUCKETS as u64) as usize] = used;
+    }
+}
+
+impl RateLimit {
+    pub const MAX_LIMITS: usize = 8;
+    pub const LEN: usize = core::mem::size_of::<Self>();
+
+    pub fn from_account_info_unchecked(account_info: &AccountInfo) -> &mut Self {
+        unsafe { &mut *(account_info.borrow_mut_data_unchecked().as_ptr() as *mut Self) }
+    }
+
+    pub fn from_account_info(account_info: &AccountInfo) -> Result<&mut Self, ProgramError> {
+        if account_info.data_len() < Self::LEN {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        Ok(Self::from_account_info_unchecked(account_info))
+    }
+
+    pub fn position(&self, mint: &Pubkey) -> Option<usize> {
+        self.limits[..self.limit_count as usize]
+            .iter()
+            .position(|limit| limit.mint == *mint)
+    }
+
+    /// Counts `amount` of `mint` leaving the vault against its cap
+    pub fn record_outflow(&mut self, mint: &Pubkey, amount: u64, now: u64) -> Result<(), ProgramError> {
+        let Some(index) = self.position(mint) else {
+            return Ok(());
+        };
+        let limit = &mut self.limits[index];
+
+        let current = limit.advance(now);
+        let used = limit.used().checked_add(amount).ok_or(ProgramError::ArithmeticOverflow)?;
+        if used > limit.max_outflow {
+            return Err(ProgramError::InsufficientFunds);
+        }
+        // Cannot overflow, the sum of all buckets is at most `max_outflow`
+        limit.buckets[(current % AssetRateLimit::BUCKETS as u64) as usize] += amount;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SOL: Pubkey = [0; 32];
+    const MINT: Pubkey = [7; 32];
+
+    /// A rate limit capping SOL at 1_000 per 70 seconds (sub-windows of 10 seconds)
+    fn sol_limit() -> RateLimit {
+        let empty = AssetRateLimit {
+            mint: Pubkey::default(),
+            max_outflow: 0,
+            window: 0,
+            buckets: [0; AssetRateLimit::BUCKETS],
+            last_bucket: 0,
+        };
+        let mut rate_limit = RateLimit {
+            multisig: Pubkey::default(),
+            limits: [empty; RateLimit::MAX_LIMITS],
+            vault_index: 0,
+            limit_count: 1,
+            bump: 0,
+        };
+        rate_limit.limits[0] = AssetRateLimit {
+            mint: SOL,
+            max_outflow: 1_000,
+            window: 70,
+            ..empty
+        };
+        rate_limit
+    }
+
+    #[test]
+    fn assets_without_a_cap_are_not_limited() {
+        let mut rate_limit = sol_limit();
+        assert!(rate_limit.record_outflow(&MINT, u64::MAX, 0).is_ok());
+    }
+
+    #[test]
+    fn outflow_up_to_the_cap_is_allowed_and_beyond_rejected() {
+        let mut rate_limit = sol_limit();
+        assert!(rate_limit.record_outflow(&SOL, 600, 0).is_ok());
+        assert!(rate_limit.record_outflow(&SOL, 400, 0).is_ok());
+        assert_eq!(rate_limit.limits[0].used(), 1_000);
+        assert_eq!(rate_limit.record_outflow(&SOL, 1, 0), Err(ProgramError::InsufficientFunds));
+    }
+
+    #[test]
+    fn outflow_counts_for_the_whole_window() {
+        let mut rate_limit = sol_limit();
+        assert!(rate_limit.record_outflow(&SOL, 1_000, 1_000).is_ok());
+
+        // Nothing expires early, even a window later the sub-window is still counted
+        for now in [1_035, 1_069, 1_070, 1_079] {
+            assert_eq!(rate_limit.record_outflow(&SOL, 1, now), Err(ProgramError::InsufficientFunds));
+        }
+        assert!(rate_limit.record_outflow(&SOL, 1_000, 1_080).is_ok());
+    }
+
+    #[test]
+    fn no_window_sees_more_than_the_cap_leave() {
+        let mut rate_limit = sol_limit();
+        let mut outflows = Vec::new();
+        for now in (0..1_000).step_by(3) {
+            if rate_limit.record_outflow(&SOL, 90, now).is_ok() {
+                outflows.push(now);
+            }
+        }
+
+        assert!(outflows.len() > 100);
+        for start in 0..1_000 {
+            let in_window = outflows.iter().filter(|now| (start..=start + 70).contains(*now)).count();
+            assert!(in_window as u64 * 90 <= 1_000);
+        }
+    }
+
+    #[test]
+    fn a_long_pause_clears_past_outflow() {
+        let mut rate_limit = sol_limit();
+        assert!(rate_limit.record_outflow(&SOL, 1_000, 1_000).is_ok());
+        assert!(rate_limit.record_outflow(&SOL, 0, 50_000).is_ok());
+        assert_eq!(rate_limit.limits[0].used(), 0);
+    }
+
+    #[test]
+    fn changing_the_window_keeps_past_outflow() {
+        let mut rate_limit = sol_limit();
+        assert!(rate_limit.record_outflow(&SOL, 800, 1_000).is_ok());
+
+        rate_limit.limits[0].set_window(1_000, 1_005);
+        assert_eq!(rate_limit.limits[0].used(), 800);
+        assert_eq!(rate_limit.record_outflow(&SOL, 201, 1_500), Err(ProgramError::InsufficientFunds));
+        assert!(rate_limit.record_outflow(&SOL, 200, 1_500).is_ok());
+    }
+
+    #[test]
+    fn overflowing_amounts_are_rejected() {
+        let mut rate_limit = sol_limit();
+        assert!(rate_limit.record_outflow(&SOL, 1, 0).is_ok());
+        assert_eq!(rate_limit.record_outflow(&SOL, u64::MAX, 0), Err(ProgramError::ArithmeticOverflow));
+    }
+}
@@ -0,0 +1,29 @@
+use pinocchio::{account_info::AccountInfo, program_error::ProgramError, pubkey::Pubkey};
+
+/// Proof that a multisig approved a message. Its existence (owned by this program at
+/// the expected address) is the attestation other programs and verifiers check
+/// Created by an `ApproveMessage` proposal
+/// PDA: [b"signed_message", multisig, hash]
+#[repr(C)]
+pub struct SignedMessage {
+    pub multisig: Pubkey,  // Multisig that approved the message
+    pub hash: [u8; 32],    // Hash of the approved message
+    pub proposal_id: u64,  // Proposal that approved it
+    pub approved_at: u64,  // Unix timestamp of the approval
+    pub bump: u8,          // PDA bump for this account
+}
+
+impl SignedMessage {
+    pub const LEN: usize = core::mem::size_of::<Self>();
+
+    pub fn from_account_info_unchecked(account_info: &AccountInfo) -> &mut Self {
+        unsafe { &mut *(account_info.borrow_mut_data_unchecked().as_ptr() as *mut Self) }
+    }
+
+    pub fn from_account_info(account_info: &AccountInfo) -> Result<&mut Self, ProgramError> {
+        if account_info.data_len() < Self::LEN {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        Ok(Self::from_account_info_unchecked(account_info))
+    }
+}
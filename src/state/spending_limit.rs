@@ -0,0 +1,133 @@
+use pinocchio::{account_info::AccountInfo, program_error::ProgramError, pubkey::Pubkey};
+
+/// Lets designated members withdraw up to `amount` per period from a vault without a vote
+/// Created by a `CreateSpendingLimit` proposal and draws from that proposal's vault
+/// PDA: [b"spending_limit", proposal]
+#[repr(C)]
+pub struct SpendingLimit {
+    pub proposal: Pubkey,       // Proposal that created the limit (seed of this PDA)
+    pub multisig: Pubkey,       // Multisig the limit belongs to
+    pub mint: Pubkey,           // Token mint, or the default pubkey for SOL
+    pub members: [Pubkey; 10],  // Members allowed to use the limit
+    pub amount: u64,            // Allowance per period
+    pub remaining_amount: u64,  // Allowance left in the current period
+    pub last_reset: u64,        // Start of the current period (unix timestamp)
+    pub proposal_id: u64,       // Proposal that created the limit
+    pub member_count: u8,       // Number of entries in `members`
+    pub period: SpendingPeriod, // How often the allowance resets
+    pub vault_index: u8,        // Vault the funds are taken from (0 = treasury)
+    pub vault_bump: u8,         // Bump of that vault PDA
+    pub bump: u8,               // PDA bump for this account
+}
+
+impl SpendingLimit {
+    pub const LEN: usize = core::mem::size_of::<Self>();
+
+    pub fn from_account_info_unchecked(account_info: &AccountInfo) -> &mut Self {
+        unsafe { &mut *(account_info.borrow_mut_data_unchecked().as_ptr() as *mut Self) }
+    }
+
+    pub fn from_account_info(account_info: &AccountInfo) -> Result<&mut Self, ProgramError> {
+        if account_info.data_len() < Self::LEN {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        Ok(Self::from_account_info_unchecked(account_info))
+    }
+
+    /// Restores the full allowance once one or more periods have elapsed
+    /// `last_reset` advances by whole periods so resets stay on a fixed schedule
+    pub fn reset_if_elapsed(&mut self, now: u64) {
+        let period = self.period.seconds();
+        if now >= self.last_reset + period {
+            let elapsed_periods = (now - self.last_reset) / period;
+            self.last_reset += elapsed_periods * period;
+            self.remaining_amount = self.amount;
+        }
+    }
+}
+
+#[repr(u8)]
+#[derive(Clone, Copy, PartialEq)]
+pub enum SpendingPeriod {
+    Day = 0,
+    Week = 1,
+    Month = 2, // 30 days
+}
+
+impl SpendingPeriod {
+    pub fn seconds(&self) -> u64 {
+        match self {
+            SpendingPeriod::Day => 86_400,
+            SpendingPeriod::Week => 7 * 86_400,
+            SpendingPeriod::Month => 30 * 86_400,
+        }
+    }
+}
+
+impl TryFrom<&u8> for SpendingPeriod {
+    type Error = ProgramError;
+
+    fn try_from(value: &u8) -> Result<Self, Self::Error> {
+        match *value {
+            0 => Ok(SpendingPeriod::Day),
+            1 => Ok(SpendingPeriod::Week),
+            2 => Ok(SpendingPeriod::Month),
+            _ => Err(ProgramError::InvalidInstructionData),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DAY: u64 = 86_400;
+
+    fn limit(period: SpendingPeriod, last_reset: u64, remaining_amount: u64) -> SpendingLimit {
+        SpendingLimit {
+            proposal: Pubkey::default(),
+            multisig: Pubkey::default(),
+            mint: Pubkey::default(),
+            members: [Pubkey::default(); 10],
+            amount: 1_000,
+            remaining_amount,
+            last_reset,
+            proposal_id: 0,
+            member_count: 0,
+            period,
+            vault_index: 0,
+            vault_bump: 0,
+            bump: 0,
+        }
+    }
+
+    #[test]
+    fn keeps_the_allowance_until_a_full_period_elapsed() {
+        let mut limit = limit(SpendingPeriod::Day, 10_000, 250);
+        limit.reset_if_elapsed(10_000 + DAY - 1);
+        assert_eq!(limit.remaining_amount, 250);
+        assert_eq!(limit.last_reset, 10_000);
+    }
+
+    #[test]
+    fn resets_exactly_at_the_period_boundary() {
+        let mut limit = limit(SpendingPeriod::Week, 10_000, 250);
+        limit.reset_if_elapsed(10_000 + 7 * DAY);
+        assert_eq!(limit.remaining_amount, 1_000);
+        assert_eq!(limit.last_reset, 10_000 + 7 * DAY);
+    }
+
+    #[test]
+    fn skipped_periods_keep_the_reset_schedule() {
+        let mut limit = limit(SpendingPeriod::Month, 10_000, 0);
+        limit.reset_if_elapsed(10_000 + 3 * 30 * DAY + 500);
+        assert_eq!(limit.remaining_amount, 1_000);
+        // Aligned on the original schedule, not on the time of the call
+        assert_eq!(limit.last_reset, 10_000 + 3 * 30 * DAY);
+
+        // The next reset is a full period after the aligned start
+        limit.remaining_amount = 0;
+        limit.reset_if_elapsed(10_000 + 4 * 30 * DAY - 1);
+        assert_eq!(limit.remaining_amount, 0);
+    }
+}
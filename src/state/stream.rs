@@ -0,0 +1,108 @@
+use pinocchio::{account_info::AccountInfo, program_error::ProgramError, pubkey::Pubkey};
+
+/// Funds escrowed from a vault that vest linearly to a recipient
+/// SOL is held by this account itself, tokens by `escrow` (a token account owned by it)
+/// Created by a `CreateStream` proposal
+/// PDA: [b"stream", proposal]
+#[repr(C)]
+pub struct Stream {
+    pub proposal: Pubkey,  // Proposal that created the stream (seed of this PDA)
+    pub multisig: Pubkey,  // Multisig the stream belongs to
+    pub mint: Pubkey,      // Token mint, or the default pubkey for SOL
+    pub recipient: Pubkey, // Recipient wallet (owner of the destination token account for tokens)
+    pub escrow: Pubkey,    // Token account holding the escrowed tokens (default pubkey for SOL)
+    pub amount: u64,       // Total vested over the stream
+    pub claimed: u64,      // Amount already claimed by the recipient
+    pub start_time: u64,   // Unix timestamp vesting starts at
+    pub end_time: u64,     // Unix timestamp at which `amount` is fully vested
+    pub vault_index: u8,   // Vault that funded the stream (0 = treasury)
+    pub vault_bump: u8,    // Bump of that vault PDA
+    pub bump: u8,          // PDA bump for this account
+}
+
+impl Stream {
+    pub const LEN: usize = core::mem::size_of::<Self>();
+
+    pub fn from_account_info_unchecked(account_info: &AccountInfo) -> &mut Self {
+        unsafe { &mut *(account_info.borrow_mut_data_unchecked().as_ptr() as *mut Self) }
+    }
+
+    pub fn from_account_info(account_info: &AccountInfo) -> Result<&mut Self, ProgramError> {
+        if account_info.data_len() < Self::LEN {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        Ok(Self::from_account_info_unchecked(account_info))
+    }
+
+    /// Amount vested at `now`, growing linearly from `start_time` to `end_time`
+    pub fn vested(&self, now: u64) -> u64 {
+        if now >= self.end_time {
+            self.amount
+        } else if now <= self.start_time {
+            0
+        } else {
+            let elapsed = (now - self.start_time) as u128;
+            let duration = (self.end_time - self.start_time) as u128;
+            (self.amount as u128 * elapsed / duration) as u64
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stream(amount: u64, start_time: u64, end_time: u64) -> Stream {
+        Stream {
+            proposal: Pubkey::default(),
+            multisig: Pubkey::default(),
+            mint: Pubkey::default(),
+            recipient: Pubkey::default(),
+            escrow: Pubkey::default(),
+            amount,
+            claimed: 0,
+            start_time,
+            end_time,
+            vault_index: 0,
+            vault_bump: 0,
+            bump: 0,
+        }
+    }
+
+    #[test]
+    fn nothing_vests_until_the_start_time() {
+        let stream = stream(1_000, 5_000, 6_000);
+        assert_eq!(stream.vested(0), 0);
+        assert_eq!(stream.vested(4_999), 0);
+        assert_eq!(stream.vested(5_000), 0);
+    }
+
+    #[test]
+    fn vests_linearly_and_rounds_down() {
+        let stream = stream(1_000, 5_000, 8_000);
+        assert_eq!(stream.vested(5_001), 0);
+        assert_eq!(stream.vested(5_003), 1);
+        assert_eq!(stream.vested(6_500), 500);
+        assert_eq!(stream.vested(7_999), 999);
+    }
+
+    #[test]
+    fn fully_vested_from_the_end_time() {
+        let stream = stream(1_000, 5_000, 6_000);
+        assert_eq!(stream.vested(6_000), 1_000);
+        assert_eq!(stream.vested(u64::MAX), 1_000);
+    }
+
+    #[test]
+    fn large_amounts_do_not_overflow() {
+        let stream = stream(u64::MAX, 0, 1_000);
+        assert_eq!(stream.vested(500), u64::MAX / 2);
+    }
+
+    #[test]
+    fn a_stream_without_duration_vests_at_once() {
+        let stream = stream(1_000, 5_000, 5_000);
+        assert_eq!(stream.vested(4_999), 0);
+        assert_eq!(stream.vested(5_000), 1_000);
+    }
+}
@@ -0,0 +1,48 @@
+use pinocchio::{account_info::AccountInfo, program_error::ProgramError, pubkey::Pubkey};
+
+/// Staging area for proposal payloads too large for a single transaction
+/// Members upload the payload in chunks, then create a proposal from it once
+/// the uploaded bytes match the declared size and hash. The header below is
+/// followed by `final_size` bytes of payload.
+/// The payload bytes are whatever the creator uploads, so owner checks alone cannot tell a
+/// buffer from any other account of the program: state is always loaded through its PDA
+/// PDA: [b"buffer", multisig, creator, index]
+#[repr(C)]
+pub struct TransactionBuffer {
+    pub multisig: Pubkey,      // Multisig the payload is meant for
+    pub creator: Pubkey,       // Member uploading the payload (the only one allowed to append)
+    pub final_hash: [u8; 32],  // SHA-256 of the complete payload
+    pub final_size: u16,       // Size of the complete payload
+    pub current_size: u16,     // Bytes uploaded so far
+    pub index: u8,             // Lets a member stage several buffers at once
+    pub bump: u8,              // PDA bump for this buffer account
+}
+
+impl TransactionBuffer {
+    pub const LEN: usize = core::mem::size_of::<Self>();
+
+    /// Accounts created through CPI can be at most 10 KiB
+    pub const MAX_SIZE: usize = 10_240 - Self::LEN;
+
+    pub fn from_account_info_unchecked(account_info: &AccountInfo) -> &mut Self {
+        unsafe { &mut *(account_info.borrow_mut_data_unchecked().as_ptr() as *mut Self) }
+    }
+
+    pub fn from_account_info(account_info: &AccountInfo) -> Result<&mut Self, ProgramError> {
+        if account_info.data_len() < Self::LEN {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        Ok(Self::from_account_info_unchecked(account_info))
+    }
+
+    /// Returns the payload bytes uploaded so far
+    pub fn data<'a>(&self, account_info: &'a AccountInfo) -> &'a [u8] {
+        unsafe {
+            &account_info.borrow_data_unchecked()[Self::LEN..Self::LEN + self.current_size as usize]
+        }
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.current_size == self.final_size
+    }
+}
@@ -0,0 +1,30 @@
+use pinocchio::{account_info::AccountInfo, program_error::ProgramError, pubkey::Pubkey};
+
+/// Configuration of an indexed multisig vault
+/// Vault 0 is the original treasury (`[b"treasury", multisig]`) and uses the
+/// multisig threshold. Vaults 1 and up hold their funds at `[b"vault", multisig, index]`
+/// (a plain system account) and are configured by this account.
+/// PDA: [b"vault_config", multisig, index]
+#[repr(C)]
+pub struct Vault {
+    pub multisig: Pubkey, // Multisig the vault belongs to
+    pub threshold: u64,   // Approvals required for proposals targeting this vault
+    pub index: u8,        // Index of the vault (1 and up)
+    pub vault_bump: u8,   // Bump for the [b"vault", multisig, index] PDA
+    pub config_bump: u8,  // Bump for this config PDA
+}
+
+impl Vault {
+    pub const LEN: usize = core::mem::size_of::<Self>();
+
+    pub fn from_account_info_unchecked(account_info: &AccountInfo) -> &mut Self {
+        unsafe { &mut *(account_info.borrow_mut_data_unchecked().as_ptr() as *mut Self) }
+    }
+
+    pub fn from_account_info(account_info: &AccountInfo) -> Result<&mut Self, ProgramError> {
+        if account_info.data_len() < Self::LEN {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        Ok(Self::from_account_info_unchecked(account_info))
+    }
+}
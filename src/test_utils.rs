@@ -0,0 +1,90 @@
+//! Accounts for unit tests of the instruction handlers, laid out the way the runtime
+//! serializes them into the program input
+
+use pinocchio::{account_info::AccountInfo, pubkey::Pubkey};
+
+use crate::state::{Multisig, Proposal, ProposalStatus};
+use crate::utils::create_program_address;
+
+/// Header the runtime writes before the data of each account: borrow state, signer,
+/// writable and executable flags, resize delta, key, owner, lamports and data length
+const HEADER_LEN: usize = 88;
+
+/// Borrow state of an account that is not borrowed (nor a duplicate)
+const NOT_BORROWED: u8 = u8::MAX;
+
+pub struct TestAccount {
+    // u64 words keep the header and the data 8-byte aligned, as in the input buffer
+    buffer: Vec<u64>,
+}
+
+impl TestAccount {
+    /// A writable account of `data_len` zeroed bytes
+    pub fn new(key: Pubkey, owner: Pubkey, data_len: usize) -> Self {
+        let mut account = Self { buffer: vec![0u64; (HEADER_LEN + data_len).div_ceil(8)] };
+        let header = account.header();
+        header[0] = NOT_BORROWED;
+        header[2] = 1;
+        header[8..40].copy_from_slice(&key);
+        header[40..72].copy_from_slice(&owner);
+        header[80..88].copy_from_slice(&(data_len as u64).to_le_bytes());
+        account
+    }
+
+    pub fn signer(mut self) -> Self {
+        self.header()[1] = 1;
+        self
+    }
+
+    pub fn with_lamports(mut self, lamports: u64) -> Self {
+        self.header()[72..80].copy_from_slice(&lamports.to_le_bytes());
+        self
+    }
+
+    pub fn info(&mut self) -> AccountInfo {
+        // `AccountInfo` is a pointer to the header
+        unsafe { core::mem::transmute::<*mut u64, AccountInfo>(self.buffer.as_mut_ptr()) }
+    }
+
+    /// The account data read as `T` (one of the program's account structs)
+    pub fn state<T>(&mut self) -> &mut T {
+        unsafe { &mut *(self.buffer.as_mut_ptr().add(HEADER_LEN / 8) as *mut T) }
+    }
+
+    fn header(&mut self) -> &mut [u8] {
+        unsafe { core::slice::from_raw_parts_mut(self.buffer.as_mut_ptr() as *mut u8, HEADER_LEN) }
+    }
+}
+
+/// Address of this program derived from `seeds` (the last one being the bump)
+pub fn program_address(seeds: &[&[u8]]) -> Pubkey {
+    create_program_address(seeds, &crate::ID).unwrap()
+}
+
+/// Multisig of `creator` at its PDA, with `members` and `threshold`
+pub fn multisig_account(creator: &Pubkey, members: &[Pubkey], threshold: u64) -> TestAccount {
+    let bump = 255;
+    let key = program_address(&[b"multisig", creator, &[bump]]);
+    let mut account = TestAccount::new(key, crate::ID, Multisig::LEN);
+    let multisig = account.state::<Multisig>();
+    multisig.creator = *creator;
+    multisig.config_bump = bump;
+    multisig.member_count = members.len() as u8;
+    multisig.member_keys[..members.len()].copy_from_slice(members);
+    multisig.threshold = threshold;
+    account
+}
+
+/// Proposal `id` of `multisig` at its PDA, as `create_proposal` leaves it
+pub fn proposal_account(multisig: &Pubkey, id: u64, status: ProposalStatus) -> TestAccount {
+    let bump = 255;
+    let key = program_address(&[b"proposal", multisig, &id.to_le_bytes(), &[bump]]);
+    let mut account = TestAccount::new(key, crate::ID, Proposal::LEN);
+    let proposal = account.state::<Proposal>();
+    proposal.multisig = *multisig;
+    proposal.id = id;
+    proposal.bump = bump;
+    proposal.status = status;
+    proposal.votes = [255; 20];
+    account
+}
@@ -0,0 +1,167 @@
+use pinocchio::{
+    account_info::AccountInfo,
+    cpi::invoke_signed,
+    instruction::{AccountMeta, Instruction, Signer},
+    program_error::ProgramError,
+    pubkey::Pubkey,
+    ProgramResult,
+};
+use pinocchio_log::log;
+
+pub const TOKEN_PROGRAM_ID: Pubkey = pinocchio_pubkey::pubkey!("TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA");
+pub const TOKEN_2022_PROGRAM_ID: Pubkey = pinocchio_pubkey::pubkey!("TokenzQdBNbLqP5VEhdkAS6EPFLC1PHnBqCXEpPxuEb");
+
+/// Size of an SPL token account (without Token-2022 extensions)
+const TOKEN_ACCOUNT_LEN: usize = 165;
+
+/// Computes the SHA-256 hash of `data` using the `sol_sha256` syscall
+pub fn sha256(data: &[u8]) -> [u8; 32] {
+    #[cfg(target_os = "solana")]
+    {
+        let mut hash = [0u8; 32];
+        // The syscall takes a list of byte slices to hash in sequence
+        let vals = [data];
+        unsafe {
+            pinocchio::syscalls::sol_sha256(vals.as_ptr() as *const u8, vals.len() as u64, hash.as_mut_ptr());
+        }
+        hash
+    }
+
+    // Unit tests run on the host, where the syscall is not available
+    #[cfg(all(not(target_os = "solana"), test))]
+    {
+        use sha2::Digest;
+        sha2::Sha256::digest(data).into()
+    }
+
+    #[cfg(all(not(target_os = "solana"), not(test)))]
+    {
+        core::hint::black_box(data);
+        unreachable!("sha256 is only available on target `solana`")
+    }
+}
+
+/// Derives the program address for `seeds` (the last one being the bump), like
+/// `pubkey::create_program_address`
+/// Unit tests run on the host, where the syscall is not available: there the address is
+/// hashed the same way but not checked to be off the curve
+pub fn create_program_address(seeds: &[&[u8]], program_id: &Pubkey) -> Result<Pubkey, ProgramError> {
+    #[cfg(not(test))]
+    {
+        pinocchio::pubkey::create_program_address(seeds, program_id)
+    }
+
+    #[cfg(test)]
+    {
+        use sha2::Digest;
+        let mut hasher = sha2::Sha256::new();
+        for seed in seeds {
+            hasher.update(seed);
+        }
+        hasher.update(program_id);
+        hasher.update(b"ProgramDerivedAddress");
+        Ok(hasher.finalize().into())
+    }
+}
+
+/// Finds the program address and bump for `seeds`, like `pubkey::find_program_address`
+/// Unit tests run on the host, where the syscall is not available: there the bump is
+/// always 255 (see `create_program_address`)
+pub fn find_program_address(seeds: &[&[u8]], program_id: &Pubkey) -> (Pubkey, u8) {
+    #[cfg(not(test))]
+    {
+        pinocchio::pubkey::find_program_address(seeds, program_id)
+    }
+
+    #[cfg(test)]
+    {
+        let bump = u8::MAX;
+        let mut with_bump = seeds.to_vec();
+        let bump_seed = [bump];
+        with_bump.push(&bump_seed);
+        (create_program_address(&with_bump, program_id).unwrap(), bump)
+    }
+}
+
+/// Transfers SOL out of a vault (a system account), signed by the vault
+pub fn transfer_sol(vault: &AccountInfo, destination: &AccountInfo, lamports: u64, signer: &Signer) -> ProgramResult {
+    pinocchio_system::instructions::Transfer {
+        from: vault,
+        to: destination,
+        lamports,
+    }
+    .invoke_signed(core::slice::from_ref(signer))
+}
+
+/// Transfers SPL tokens out of a token account owned by a vault, signed by the vault
+/// Checks that `source` holds `mint` and is owned by the vault
+pub fn transfer_token(
+    source: &AccountInfo,
+    destination: &AccountInfo,
+    vault: &AccountInfo,
+    mint: &Pubkey,
+    token_program: &AccountInfo,
+    amount: u64,
+    signer: &Signer,
+) -> ProgramResult {
+    if token_program.key() != &TOKEN_PROGRAM_ID && token_program.key() != &TOKEN_2022_PROGRAM_ID {
+        log!("Error: Unsupported token program.");
+        return Err(ProgramError::IncorrectProgramId);
+    }
+
+    {
+        let data = source.try_borrow_data()?;
+        if source.owner() != token_program.key()
+            || data.len() < TOKEN_ACCOUNT_LEN
+            || &data[0..32] != mint.as_ref()
+            || &data[32..64] != vault.key().as_ref()
+        {
+            log!("Error: Source must be a token account of the mint owned by the vault.");
+            return Err(ProgramError::InvalidAccountData);
+        }
+    }
+
+    // SPL token `Transfer` instruction: tag 3 followed by the amount
+    let mut data = [0u8; 9];
+    data[0] = 3;
+    data[1..].copy_from_slice(&amount.to_le_bytes());
+
+    invoke_signed(
+        &Instruction {
+            program_id: token_program.key(),
+            accounts: &[
+                AccountMeta::writable(source.key()),
+                AccountMeta::writable(destination.key()),
+                AccountMeta::readonly_signer(vault.key()),
+            ],
+            data: &data,
+        },
+        &[source, destination, vault],
+        core::slice::from_ref(signer),
+    )
+}
+
+/// Pays `amount` out of funds escrowed by a program-owned `holder` account
+/// SOL is debited from `holder` directly, tokens are sent from `token_escrow` (a token
+/// account owned by `holder`) with `holder` as signer
+pub fn pay_from_escrow(
+    holder: &AccountInfo,
+    token_escrow: Option<&AccountInfo>,
+    destination: &AccountInfo,
+    program: &AccountInfo,
+    mint: &Pubkey,
+    amount: u64,
+    signer: &Signer,
+) -> ProgramResult {
+    if mint == &Pubkey::default() {
+        *holder.try_borrow_mut_lamports()? -= amount;
+        *destination.try_borrow_mut_lamports()? += amount;
+        return Ok(());
+    }
+
+    let Some(token_escrow) = token_escrow else {
+        log!("Error: Escrow token account is required for tokens.");
+        return Err(ProgramError::NotEnoughAccountKeys);
+    };
+    transfer_token(token_escrow, destination, holder, mint, program, amount, signer)
+}